@@ -4,5 +4,305 @@ use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, TokenAccount};
 use solana_maths::Rate;
 
-// use crate::adapters::
+use crate::{
+    adapters::jet_program::{self, Amount, Market, Reserve},
+    errors::ErrorCode,
+    impl_has_vault,
+    init_yield_source::YieldSourceInitializer,
+    reconcile::LendingMarket,
+    refresh::Refresher,
+    reserves::{Provider, ReserveAccessor},
+    state::Vault,
+};
 
+#[derive(Accounts)]
+pub struct JetAccounts<'info> {
+    /// Vault state account
+    /// Checks that the accounts passed in are correct
+    #[account(
+        mut,
+        has_one = vault_authority,
+        has_one = vault_reserve_token,
+    )]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Authority that the vault uses for lp token mints/burns and transfers to/from downstream assets
+    pub vault_authority: AccountInfo<'info>,
+
+    /// Token account for the vault's reserve tokens
+    #[account(mut)]
+    pub vault_reserve_token: Box<Account<'info, TokenAccount>>,
+
+    /// Token account for the vault's jet lp tokens
+    #[account(mut)]
+    pub vault_jet_lp_token: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        executable,
+        address = jet_program::ID,
+    )]
+    pub jet_program: AccountInfo<'info>,
+
+    pub jet_market: AccountLoader<'info, Market>,
+
+    pub jet_market_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = jet_reserve.load()?.market == jet_market.key() @ ErrorCode::InvalidAccount,
+    )]
+    pub jet_reserve: AccountLoader<'info, Reserve>,
+
+    #[account(mut)]
+    pub jet_reserve_token: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub jet_lp_mint: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl_has_vault!(JetAccounts<'_>);
+
+impl<'info> LendingMarket for JetAccounts<'info> {
+    fn deposit(&self, amount: u64) -> Result<()> {
+        let context = CpiContext::new(
+            self.jet_program.clone(),
+            jet_program::DepositTokens {
+                market: self.jet_market.to_account_info(),
+                market_authority: self.jet_market_authority.clone(),
+                reserve: self.jet_reserve.to_account_info(),
+                vault: self.jet_reserve_token.clone(),
+                deposit_note_mint: self.jet_lp_mint.clone(),
+                depositor: self.vault_authority.clone(),
+                deposit_note_account: self.vault_jet_lp_token.to_account_info(),
+                deposit_source: self.vault_reserve_token.to_account_info(),
+                token_program: self.token_program.to_account_info(),
+            },
+        );
+
+        match amount {
+            0 => Ok(()),
+            _ => jet_program::deposit_tokens(
+                context.with_signer(&[&self.vault.authority_seeds()]),
+                Amount::from_tokens(amount),
+            ),
+        }
+    }
+
+    fn redeem(&self, amount: u64) -> Result<()> {
+        let context = CpiContext::new(
+            self.jet_program.clone(),
+            jet_program::WithdrawTokens {
+                market: self.jet_market.to_account_info(),
+                market_authority: self.jet_market_authority.clone(),
+                reserve: self.jet_reserve.to_account_info(),
+                vault: self.jet_reserve_token.clone(),
+                deposit_note_mint: self.jet_lp_mint.clone(),
+                depositor: self.vault_authority.clone(),
+                deposit_note_account: self.vault_jet_lp_token.to_account_info(),
+                withdraw_account: self.vault_reserve_token.to_account_info(),
+                jet_program: self.jet_program.clone(),
+                token_program: self.token_program.to_account_info(),
+            },
+        );
+
+        match amount {
+            0 => Ok(()),
+            _ => jet_program::withdraw_tokens(
+                context.with_signer(&[&self.vault.authority_seeds()]),
+                Amount::from_deposit_notes(amount),
+            ),
+        }
+    }
+
+    fn convert_amount_reserve_to_lp(&self, amount: u64) -> Result<u64> {
+        let market = self.jet_market.load()?;
+        let reserve = self.jet_reserve.load()?;
+        market.tokens_to_deposit_notes(reserve.index, self.clock.slot, amount)
+    }
+
+    fn convert_amount_lp_to_reserve(&self, amount: u64) -> Result<u64> {
+        let market = self.jet_market.load()?;
+        let reserve = self.jet_reserve.load()?;
+        market.deposit_notes_to_tokens(reserve.index, self.clock.slot, amount)
+    }
+
+    fn reserve_tokens_in_vault(&self) -> u64 {
+        self.vault_reserve_token.amount
+    }
+
+    fn lp_tokens_in_vault(&self) -> u64 {
+        self.vault_jet_lp_token.amount
+    }
+
+    fn provider(&self) -> Provider {
+        Provider::Jet
+    }
+
+    fn yield_source_index(&self) -> Result<usize> {
+        self.vault.yield_source_index(
+            Provider::Jet,
+            self.jet_reserve.key(),
+            self.vault_jet_lp_token.key(),
+        )
+    }
+}
+
+impl ReserveAccessor for Reserve {
+    fn utilization_rate(&self) -> Result<Rate> {
+        Ok(Rate::from_bips(self.utilization_rate_bps()?))
+    }
+
+    fn borrow_rate(&self) -> Result<Rate> {
+        Ok(Rate::from_bips(self.borrow_rate_bps()?))
+    }
+
+    fn reserve_with_deposit(&self, allocation: u64) -> Result<Box<dyn ReserveAccessor>> {
+        let mut reserve = Box::new(*self);
+        reserve.deposit(allocation, 0);
+        Ok(reserve)
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(bump: u8)]
+pub struct InitializeJet<'info> {
+    #[account(mut, has_one = owner, has_one = vault_authority)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    pub vault_authority: AccountInfo<'info>,
+
+    /// Token account for the vault's jet lp tokens
+    #[account(init, payer = payer, seeds = [vault.key().as_ref(), jet_lp_token_mint.key().as_ref()], bump, token::authority = vault_authority, token::mint = jet_lp_token_mint)]
+    pub vault_jet_lp_token: Box<Account<'info, TokenAccount>>,
+
+    /// Mint of the jet lp token
+    pub jet_lp_token_mint: AccountInfo<'info>,
+
+    /// Jet reserve that the lp token mint belongs to
+    #[account(
+        constraint = jet_reserve.load()?.deposit_note_mint == jet_lp_token_mint.key() @ ErrorCode::InvalidAccount,
+    )]
+    pub jet_reserve: AccountLoader<'info, Reserve>,
+
+    pub owner: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+
+    pub rent: Sysvar<'info, Rent>,
+}
+
+impl<'info> YieldSourceInitializer<'info> for InitializeJet<'info> {
+    fn initialize_yield_source(&mut self) -> Result<()> {
+        self.vault.add_yield_source(
+            Provider::Jet,
+            jet_program::ID,
+            self.jet_reserve.key(),
+            self.vault_jet_lp_token.key(),
+        )?;
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct RefreshJet<'info> {
+    /// Vault state account
+    /// Checks that the accounts passed in are correct
+    #[account(mut)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Token account for the vault's jet lp tokens
+    pub vault_jet_lp_token: Box<Account<'info, TokenAccount>>,
+
+    #[account(executable, address = jet_program::ID)]
+    pub jet_program: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub jet_market: AccountLoader<'info, Market>,
+
+    pub jet_market_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        constraint = jet_reserve.load()?.market == jet_market.key() @ ErrorCode::InvalidAccount,
+    )]
+    pub jet_reserve: AccountLoader<'info, Reserve>,
+
+    #[account(mut)]
+    pub jet_fee_note_vault: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub jet_deposit_note_mint: AccountInfo<'info>,
+
+    pub jet_pyth: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl_has_vault!(RefreshJet<'_>);
+
+impl<'info> RefreshJet<'info> {
+    fn jet_refresh_reserve_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, jet_program::RefreshReserve<'info>> {
+        CpiContext::new(
+            self.jet_program.clone(),
+            jet_program::RefreshReserve {
+                market: self.jet_market.to_account_info(),
+                market_authority: self.jet_market_authority.clone(),
+                reserve: self.jet_reserve.to_account_info(),
+                fee_note_vault: self.jet_fee_note_vault.clone(),
+                deposit_note_mint: self.jet_deposit_note_mint.clone(),
+                pyth_oracle_price: self.jet_pyth.clone(),
+                token_program: self.token_program.to_account_info(),
+            },
+        )
+    }
+}
+
+impl<'info> Refresher<'info> for RefreshJet<'info> {
+    fn update_actual_allocation(
+        &mut self,
+        _remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let index = self.vault.yield_source_index(
+            Provider::Jet,
+            self.jet_reserve.key(),
+            self.vault_jet_lp_token.key(),
+        )?;
+
+        #[cfg(feature = "debug")]
+        msg!("Refreshing jet");
+
+        jet_program::refresh_reserve(self.jet_refresh_reserve_context())?;
+
+        let jet_value = {
+            let market = self.jet_market.load()?;
+            let reserve = self.jet_reserve.load()?;
+            market.deposit_notes_to_tokens(
+                reserve.index,
+                self.clock.slot,
+                self.vault_jet_lp_token.amount,
+            )?
+        };
+
+        #[cfg(feature = "debug")]
+        msg!("Refresh jet reserve token value: {}", jet_value);
+
+        self.vault.yield_sources[index]
+            .actual_allocation
+            .update(jet_value, &self.clock);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,412 @@
+//! Account layouts and instructions of the Jet v1 lending program that the jet adapter uses
+//!
+//! These are vendored instead of depending on the jet crate, which pins an older anchor version
+//! Only the fields that the adapter reads are named, the rest of each account is kept as padding
+//! so that the sizes and offsets match the accounts of the Jet program
+
+use anchor_lang::{prelude::*, solana_program, Discriminator, ZeroCopy};
+use boolinator::Boolinator;
+use bytemuck::{Pod, Zeroable};
+
+use crate::errors::ErrorCode;
+
+declare_id!("JPv1rCqrhagNNmJVM5J1he7msQ5ybtvE1nNuHpDHMNU");
+
+const DEPOSIT_TOKENS_DISCRIMINATOR: [u8; 8] = [176, 83, 229, 18, 191, 143, 176, 150];
+const WITHDRAW_TOKENS_DISCRIMINATOR: [u8; 8] = [2, 4, 225, 61, 19, 182, 106, 170];
+const REFRESH_RESERVE_DISCRIMINATOR: [u8; 8] = [2, 218, 138, 235, 79, 201, 25, 102];
+
+/// Decimal fixed point number that Jet stores as a little endian 192 bit integer
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(transparent)]
+pub struct Number([u64; 3]);
+
+impl Number {
+    const PRECISION: u128 = 1_000_000_000_000_000;
+
+    /// Returns the raw fixed point value if it fits in 128 bits
+    fn to_scaled_u128(self) -> Result<u128> {
+        (self.0[2] == 0)
+            .as_some(((self.0[1] as u128) << 64) | self.0[0] as u128)
+            .ok_or_else(|| ErrorCode::OverflowError.into())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub enum AmountUnits {
+    Tokens,
+    DepositNotes,
+    LoanNotes,
+}
+
+/// Amount argument of the Jet deposit and withdraw instructions
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct Amount {
+    pub units: AmountUnits,
+    pub value: u64,
+}
+
+impl Amount {
+    pub fn from_tokens(value: u64) -> Self {
+        Self {
+            units: AmountUnits::Tokens,
+            value,
+        }
+    }
+
+    pub fn from_deposit_notes(value: u64) -> Self {
+        Self {
+            units: AmountUnits::DepositNotes,
+            value,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct CachedReserveInfo {
+    pub price: Number,
+    pub deposit_note_exchange_rate: Number,
+    pub loan_note_exchange_rate: Number,
+    pub min_collateral_ratio: Number,
+    pub liquidation_bonus: u16,
+    _reserved: [u8; 158],
+}
+
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct ReserveInfo {
+    pub reserve: Pubkey,
+    _reserved: [u8; 80],
+    pub last_updated: u64,
+    pub invalidated: u8,
+    _reserved_cache: [u8; 7],
+    pub cache: CachedReserveInfo,
+}
+
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct Market {
+    pub version: u32,
+    pub quote_exponent: i32,
+    pub quote_currency: [u8; 15],
+    pub authority_bump_seed: [u8; 1],
+    pub authority_seed: Pubkey,
+    pub market_authority: Pubkey,
+    pub owner: Pubkey,
+    pub quote_token_mint: Pubkey,
+    pub flags: u64,
+    _reserved: [u8; 352],
+    pub reserves: [ReserveInfo; 32],
+}
+
+impl Market {
+    /// Returns the deposit note exchange rate of the reserve at `index`
+    /// Jet only uses the cached rate in the slot that the reserve was refreshed in
+    fn deposit_note_exchange_rate(&self, index: u16, slot: u64) -> Result<u128> {
+        let info = self
+            .reserves
+            .get(index as usize)
+            .ok_or(ErrorCode::InvalidAccount)?;
+        (info.invalidated == 0 && info.last_updated == slot).ok_or(ErrorCode::JetReserveIsStale)?;
+        info.cache.deposit_note_exchange_rate.to_scaled_u128()
+    }
+
+    /// Converts reserve tokens to deposit notes of the reserve at `index`, rounding down
+    pub fn tokens_to_deposit_notes(&self, index: u16, slot: u64, amount: u64) -> Result<u64> {
+        let rate = self.deposit_note_exchange_rate(index, slot)?;
+        (rate != 0).ok_or(ErrorCode::MathError)?;
+        (amount as u128)
+            .checked_mul(Number::PRECISION)
+            .map(|scaled| scaled / rate)
+            .and_then(|notes| u64::try_from(notes).ok())
+            .ok_or_else(|| ErrorCode::OverflowError.into())
+    }
+
+    /// Converts deposit notes of the reserve at `index` to reserve tokens, rounding down
+    pub fn deposit_notes_to_tokens(&self, index: u16, slot: u64, amount: u64) -> Result<u64> {
+        let rate = self.deposit_note_exchange_rate(index, slot)?;
+        (amount as u128)
+            .checked_mul(rate)
+            .map(|scaled| scaled / Number::PRECISION)
+            .and_then(|tokens| u64::try_from(tokens).ok())
+            .ok_or_else(|| ErrorCode::OverflowError.into())
+    }
+}
+
+impl Discriminator for Market {
+    fn discriminator() -> [u8; 8] {
+        [219, 190, 213, 55, 0, 227, 198, 154]
+    }
+}
+
+impl Owner for Market {
+    fn owner() -> Pubkey {
+        ID
+    }
+}
+
+impl ZeroCopy for Market {}
+
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct ReserveConfig {
+    pub utilization_rate_1: u16,
+    pub utilization_rate_2: u16,
+    pub borrow_rate_0: u16,
+    pub borrow_rate_1: u16,
+    pub borrow_rate_2: u16,
+    pub borrow_rate_3: u16,
+    pub min_collateral_ratio: u16,
+    pub liquidation_premium: u16,
+    pub manage_fee_collection_threshold: u64,
+    pub manage_fee_rate: u16,
+    pub loan_origination_fee: u16,
+    pub liquidation_slippage: u16,
+    _reserved0: u16,
+    pub liquidation_dex_trade_max: u64,
+    _reserved1: [u8; 24],
+}
+
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct ReserveState {
+    pub last_updated: u64,
+    pub invalidated: u8,
+    _reserved_cache: [u8; 7],
+    pub accrued_until: i64,
+    pub outstanding_debt: Number,
+    pub uncollected_fees: Number,
+    pub total_deposits: u64,
+    pub total_deposit_notes: u64,
+    pub total_loan_notes: u64,
+    _reserved: [u8; 416],
+}
+
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct Reserve {
+    pub version: u16,
+    pub index: u16,
+    pub exponent: i32,
+    pub market: Pubkey,
+    pub pyth_oracle_price: Pubkey,
+    pub pyth_oracle_product: Pubkey,
+    pub token_mint: Pubkey,
+    pub deposit_note_mint: Pubkey,
+    pub loan_note_mint: Pubkey,
+    pub vault: Pubkey,
+    pub fee_note_vault: Pubkey,
+    pub dex_swap_tokens: Pubkey,
+    pub dex_open_orders: Pubkey,
+    pub dex_market: Pubkey,
+    _reserved0: [u8; 408],
+    pub config: ReserveConfig,
+    _reserved1: [u8; 704],
+    pub state: ReserveState,
+}
+
+impl Reserve {
+    /// Returns the utilization rate of the reserve in bps
+    pub fn utilization_rate_bps(&self) -> Result<u64> {
+        let outstanding_debt = self.state.outstanding_debt.to_scaled_u128()?;
+        let total = (self.state.total_deposits as u128)
+            .checked_mul(Number::PRECISION)
+            .and_then(|deposits| deposits.checked_add(outstanding_debt))
+            .ok_or(ErrorCode::OverflowError)?;
+        if total == 0 {
+            return Ok(0);
+        }
+        outstanding_debt
+            .checked_mul(10_000)
+            .map(|debt| (debt / total) as u64)
+            .ok_or_else(|| ErrorCode::OverflowError.into())
+    }
+
+    /// Returns the borrow rate of the reserve in bps
+    /// Jet interpolates linearly between the rates configured at 0%, both utilization
+    /// kinks and 100% utilization
+    pub fn borrow_rate_bps(&self) -> Result<u64> {
+        let config = self.config;
+        if self.state.total_deposits == 0 && self.state.outstanding_debt.to_scaled_u128()? == 0 {
+            return Ok(config.borrow_rate_1 as u64);
+        }
+
+        let utilization = self.utilization_rate_bps()?;
+        let kinks = [
+            (0, config.borrow_rate_0),
+            (config.utilization_rate_1, config.borrow_rate_1),
+            (config.utilization_rate_2, config.borrow_rate_2),
+            (10_000, config.borrow_rate_3),
+        ];
+        Ok(kinks
+            .windows(2)
+            .find(|pair| utilization <= pair[1].0 as u64)
+            .map_or(config.borrow_rate_3 as u64, |pair| {
+                let (x0, y0) = (pair[0].0 as i128, pair[0].1 as i128);
+                let (x1, y1) = (pair[1].0 as i128, pair[1].1 as i128);
+                match x1 - x0 {
+                    0 => y1 as u64,
+                    dx => (y0 + (utilization as i128 - x0) * (y1 - y0) / dx) as u64,
+                }
+            }))
+    }
+
+    /// Records a deposit the same way the Jet program does, for simulating allocations
+    pub fn deposit(&mut self, token_amount: u64, note_amount: u64) {
+        self.state.total_deposits = self.state.total_deposits.saturating_add(token_amount);
+        self.state.total_deposit_notes = self.state.total_deposit_notes.saturating_add(note_amount);
+    }
+}
+
+impl Discriminator for Reserve {
+    fn discriminator() -> [u8; 8] {
+        [43, 242, 204, 202, 26, 247, 59, 127]
+    }
+}
+
+impl Owner for Reserve {
+    fn owner() -> Pubkey {
+        ID
+    }
+}
+
+impl ZeroCopy for Reserve {}
+
+fn invoke_jet<'info, T: ToAccountMetas + ToAccountInfos<'info>>(
+    ctx: CpiContext<'_, '_, '_, 'info, T>,
+    data: Vec<u8>,
+) -> Result<()> {
+    let ix = solana_program::instruction::Instruction {
+        program_id: *ctx.program.key,
+        accounts: ctx.accounts.to_account_metas(None),
+        data,
+    };
+
+    solana_program::program::invoke_signed(
+        &ix,
+        &ToAccountInfos::to_account_infos(&ctx),
+        ctx.signer_seeds,
+    )?;
+
+    Ok(())
+}
+
+pub fn deposit_tokens<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, DepositTokens<'info>>,
+    amount: Amount,
+) -> Result<()> {
+    let mut data = DEPOSIT_TOKENS_DISCRIMINATOR.to_vec();
+    amount
+        .serialize(&mut data)
+        .map_err(|_| anchor_lang::error::ErrorCode::InstructionDidNotSerialize)?;
+    invoke_jet(ctx, data)
+}
+
+pub fn withdraw_tokens<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, WithdrawTokens<'info>>,
+    amount: Amount,
+) -> Result<()> {
+    let mut data = WITHDRAW_TOKENS_DISCRIMINATOR.to_vec();
+    amount
+        .serialize(&mut data)
+        .map_err(|_| anchor_lang::error::ErrorCode::InstructionDidNotSerialize)?;
+    invoke_jet(ctx, data)
+}
+
+pub fn refresh_reserve<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, RefreshReserve<'info>>,
+) -> Result<()> {
+    invoke_jet(ctx, REFRESH_RESERVE_DISCRIMINATOR.to_vec())
+}
+
+#[derive(Accounts)]
+pub struct DepositTokens<'info> {
+    pub market: AccountInfo<'info>,
+
+    pub market_authority: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub reserve: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub vault: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub deposit_note_mint: AccountInfo<'info>,
+
+    #[account(signer)]
+    pub depositor: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub deposit_note_account: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub deposit_source: AccountInfo<'info>,
+
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawTokens<'info> {
+    pub market: AccountInfo<'info>,
+
+    pub market_authority: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub reserve: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub vault: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub deposit_note_mint: AccountInfo<'info>,
+
+    #[account(signer)]
+    pub depositor: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub deposit_note_account: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub withdraw_account: AccountInfo<'info>,
+
+    pub jet_program: AccountInfo<'info>,
+
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshReserve<'info> {
+    #[account(mut)]
+    pub market: AccountInfo<'info>,
+
+    pub market_authority: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub reserve: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub fee_note_vault: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub deposit_note_mint: AccountInfo<'info>,
+
+    pub pyth_oracle_price: AccountInfo<'info>,
+
+    pub token_program: AccountInfo<'info>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_sizes() {
+        assert_eq!(std::mem::size_of::<ReserveInfo>(), 384);
+        assert_eq!(std::mem::size_of::<Market>(), 12800);
+        assert_eq!(std::mem::size_of::<ReserveConfig>(), 64);
+        assert_eq!(std::mem::size_of::<ReserveState>(), 512);
+        assert_eq!(std::mem::size_of::<Reserve>(), 2048);
+    }
+}
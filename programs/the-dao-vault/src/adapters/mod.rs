@@ -1,7 +1,10 @@
 pub mod jet;
+pub mod jet_program;
 pub mod port;
 pub mod solend;
+pub mod token_lending;
 
 pub use jet::*;
 pub use port::*;
 pub use solend::*;
+pub use token_lending::*;
@@ -24,8 +24,6 @@ pub struct PortAccounts<'info> {
         mut,
         has_one = vault_authority,
         has_one = vault_reserve_token,
-        has_one = vault_port_lp_token,
-        has_one = port_reserve,
     )]
     pub vault: Box<Account<'info, Vault>>,
 
@@ -145,6 +143,14 @@ impl<'info> LendingMarket for PortAccounts<'info> {
     fn provider(&self) -> Provider {
         Provider::Port
     }
+
+    fn yield_source_index(&self) -> Result<usize> {
+        self.vault.yield_source_index(
+            Provider::Port,
+            self.port_reserve.key(),
+            self.vault_port_lp_token.key(),
+        )
+    }
 }
 
 impl ReserveAccessor for Reserve {
@@ -202,8 +208,12 @@ pub struct InitializePort<'info> {
 
 impl<'info> YieldSourceInitializer<'info> for InitializePort<'info> {
     fn initialize_yield_source(&mut self) -> Result<()> {
-        self.vault.port_reserve = self.port_reserve.key();
-        self.vault.vault_port_lp_token = self.vault_port_lp_token.key();
+        self.vault.add_yield_source(
+            Provider::Port,
+            port_lending_id(),
+            self.port_reserve.key(),
+            self.vault_port_lp_token.key(),
+        )?;
         Ok(())
     }
 }
@@ -212,7 +222,7 @@ impl<'info> YieldSourceInitializer<'info> for InitializePort<'info> {
 pub struct RefreshPort<'info> {
     /// Vault state account
     /// Checks that the accounts passed in are correct
-    #[account(mut, has_one = vault_port_lp_token, has_one = port_reserve)]
+    #[account(mut)]
     pub vault: Box<Account<'info, Vault>>,
 
     /// Token account for the vault's port lp tokens
@@ -227,6 +237,8 @@ pub struct RefreshPort<'info> {
     pub clock: Sysvar<'info, Clock>,
 }
 
+impl_has_vault!(RefreshPort<'_>);
+
 impl<'info> RefreshPort<'info> {
     fn port_refresh_reserve_context(
         &self,
@@ -248,6 +260,12 @@ impl<'info> Refresher<'info> for RefreshPort<'info> {
         &mut self,
         remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
+        let index = self.vault.yield_source_index(
+            Provider::Port,
+            self.port_reserve.key(),
+            self.vault_port_lp_token.key(),
+        )?;
+
         port_anchor_adaptor::refresh_port_reserve(
             self.port_refresh_reserve_context(remaining_accounts),
         )?;
@@ -259,7 +277,9 @@ impl<'info> Refresher<'info> for RefreshPort<'info> {
         #[cfg(feature = "debug")]
         msg!("Refresh port reserve token value: {}", port_value);
 
-        self.vault.actual_allocations[Provider::Port].update(port_value, self.clock.slot);
+        self.vault.yield_sources[index]
+            .actual_allocation
+            .update(port_value, &self.clock);
 
         Ok(())
     }
@@ -5,15 +5,18 @@ use std::{
 
 use anchor_lang::{prelude::*, solana_program};
 use anchor_spl::token::{Token, TokenAccount};
-use solana_maths::Rate;
 use spl_token_lending::state::Reserve;
 
 use crate::{
+    adapters::token_lending::{
+        deposit_reserve_liquidity, redeem_reserve_collateral, refresh_reserve,
+        DepositReserveLiquidity, RedeemReserveCollateral, RefreshReserve,
+    },
     impl_has_vault,
     init_yield_source::YieldSourceInitializer,
     reconcile::LendingMarket,
     refresh::Refresher,
-    reserves::{Provider, ReserveAccessor},
+    reserves::Provider,
     state::Vault,
 };
 
@@ -21,7 +24,7 @@ use crate::{
 pub struct SolendAccounts<'info> {
     /// Vault state account
     /// Checks that the accounts passed in are correct
-    #[account(mut, has_one = vault_authority, has_one = vault_reserve_token, has_one = vault_solend_lp_token, has_one = solend_reserve, )]
+    #[account(mut, has_one = vault_authority, has_one = vault_reserve_token)]
     pub vault: Box<Account<'info, Vault>>,
 
     /// Authority that the vault uses for lp token mints/burns ans transfers to/from downstream assets
@@ -139,188 +142,16 @@ impl<'info> LendingMarket for SolendAccounts<'info> {
     fn provider(&self) -> Provider {
         Provider::Solend
     }
-}
-
-impl ReserveAccessor for Reserve {
-    fn utilization_rate(&self) -> Result<Rate> {
-        Ok(Rate::from_scaled_val(
-            self.liquidity.utilization_rate()?.to_scaled_val() as u64,
-        ))
-    }
 
-    fn borrow_rate(&self) -> Result<Rate> {
-        Ok(Rate::from_scaled_val(
-            self.current_borrow_rate()?.to_scaled_val() as u64,
-        ))
-    }
-
-    fn reserve_with_deposit(&self, allocation: u64) -> Result<Box<dyn ReserveAccessor>> {
-        let mut reserve = Box::new(self.clone());
-        reserve.liquidity.deposit(allocation)?;
-        Ok(reserve)
+    fn yield_source_index(&self) -> Result<usize> {
+        self.vault.yield_source_index(
+            Provider::Solend,
+            self.solend_reserve.key(),
+            self.vault_solend_lp_token.key(),
+        )
     }
 }
 
-pub fn deposit_reserve_liquidity<'info>(
-    ctx: CpiContext<'_, '_, '_, 'info, DepositReserveLiquidity<'info>>,
-    liquidity_amount: u64,
-) -> Result<()> {
-    let ix = spl_token_lending::instruction::deposit_reserve_liquidity(
-        *ctx.accounts.lending_program.key,
-        liquidity_amount,
-        *ctx.accounts.source_liquidity.key,
-        *ctx.accounts.destination_collateral_account.key,
-        *ctx.accounts.reserve.key,
-        *ctx.accounts.reserve_liquidity_supply.key,
-        *ctx.accounts.reserve_collateral_mint.key,
-        *ctx.accounts.lending_market.key,
-        *ctx.accounts.transfer_authority.key,
-    );
-
-    solana_program::program::invoke_signed(
-        &ix,
-        &ToAccountInfos::to_account_infos(&ctx),
-        ctx.signer_seeds,
-    )?;
-
-    Ok(())
-}
-
-pub fn redeem_reserve_collateral<'info>(
-    ctx: CpiContext<'_, '_, '_, 'info, RedeemReserveCollateral<'info>>,
-    collateral_amount: u64,
-) -> Result<()> {
-    let ix = spl_token_lending::instruction::redeem_reserve_collateral(
-        *ctx.accounts.lending_program.key,
-        collateral_amount,
-        *ctx.accounts.source_collateral.key,
-        *ctx.accounts.destination_liquidity.key,
-        *ctx.accounts.reserve.key,
-        *ctx.accounts.reserve_collateral_mint.key,
-        *ctx.accounts.reserve_liquidity_supply.key,
-        *ctx.accounts.lending_market.key,
-        *ctx.accounts.transfer_authority.key,
-    );
-
-    solana_program::program::invoke_signed(
-        &ix,
-        &ToAccountInfos::to_account_infos(&ctx),
-        ctx.signer_seeds,
-    )?;
-
-    Ok(())
-}
-
-pub fn refresh_reserve<'info>(
-    ctx: CpiContext<'_, '_, '_, 'info, RefreshReserve<'info>>,
-) -> Result<()> {
-    let ix = spl_token_lending::instruction::refresh_reserve(
-        *ctx.accounts.lending_program.key,
-        *ctx.accounts.reserve.key,
-        *ctx.accounts.pyth_reserve_liquidity_oracle.key,
-        *ctx.accounts.switchboard_reserve_liquidity_oracle.key,
-    );
-    solana_program::program::invoke_signed(
-        &ix,
-        &ToAccountInfos::to_account_infos(&ctx),
-        ctx.signer_seeds,
-    )?;
-
-    Ok(())
-}
-
-#[derive(Accounts)]
-pub struct DepositReserveLiquidity<'info> {
-    // Lending program
-    pub lending_program: AccountInfo<'info>,
-
-    // Token account for asset to deposit into reserve
-    pub source_liquidity: AccountInfo<'info>,
-
-    // Token account for reserve collateral token
-    pub destination_collateral_account: AccountInfo<'info>,
-
-    // Reserve state account
-    pub reserve: AccountInfo<'info>,
-
-    // Token mint for reserve collateral token
-    pub reserve_collateral_mint: AccountInfo<'info>,
-
-    // Reserve liquidity supply SPL token account
-    pub reserve_liquidity_supply: AccountInfo<'info>,
-
-    // Lending market
-    pub lending_market: AccountInfo<'info>,
-
-    // Lending market Authority (PDA)
-    pub lending_market_authority: AccountInfo<'info>,
-
-    // Transfer auhtority for accounts 1 and 2
-    pub transfer_authority: AccountInfo<'info>,
-
-    // Clock
-    pub clock: AccountInfo<'info>,
-
-    // Token program ID
-    pub token_program_id: AccountInfo<'info>,
-}
-
-#[derive(Accounts)]
-pub struct RedeemReserveCollateral<'info> {
-    // Lending program
-    pub lending_program: AccountInfo<'info>,
-
-    // Source token account for reserve collateral token
-    pub source_collateral: AccountInfo<'info>,
-
-    // Destination liquidity token account
-    pub destination_liquidity: AccountInfo<'info>,
-
-    // Refreshed reserve account
-    pub reserve: AccountInfo<'info>,
-
-    // Reserve collateral mint account
-    pub reserve_collateral_mint: AccountInfo<'info>,
-
-    // Reserve liquidity supply SPL Token account.
-    pub reserve_liquidity_supply: AccountInfo<'info>,
-
-    // Lending market
-    pub lending_market: AccountInfo<'info>,
-
-    // Lending market account - PDA
-    pub lending_market_authority: AccountInfo<'info>,
-
-    // User transfer authority
-    pub transfer_authority: AccountInfo<'info>,
-
-    // Clock
-    pub clock: AccountInfo<'info>,
-
-    // Token program ID
-    pub token_program_id: AccountInfo<'info>,
-}
-
-#[derive(Accounts)]
-pub struct RefreshReserve<'info> {
-    // Lending program
-    pub lending_program: AccountInfo<'info>,
-
-    // Reserve account
-    pub reserve: AccountInfo<'info>,
-
-    // Pyth reserve liquidity oracle
-    // Must be the pyth price account specified in InitReserve
-    pub pyth_reserve_liquidity_oracle: AccountInfo<'info>,
-
-    // Switchboard Reserve liquidity oracle account
-    // Must be the switchboard price account specified in InitReserve
-    pub switchboard_reserve_liquidity_oracle: AccountInfo<'info>,
-
-    // Clock
-    pub clock: AccountInfo<'info>,
-}
-
 #[derive(Clone)]
 pub struct SolendReserve(Reserve);
 
@@ -386,8 +217,12 @@ pub struct InitializeSolend<'info> {
 
 impl<'info> YieldSourceInitializer<'info> for InitializeSolend<'info> {
     fn initialize_yield_source(&mut self) -> Result<()> {
-        self.vault.solend_reserve = self.solend_reserve.key();
-        self.vault.vault_solend_lp_token = self.vault_solend_lp_token.key();
+        self.vault.add_yield_source(
+            Provider::Solend,
+            spl_token_lending::ID,
+            self.solend_reserve.key(),
+            self.vault_solend_lp_token.key(),
+        )?;
         Ok(())
     }
 }
@@ -396,7 +231,7 @@ impl<'info> YieldSourceInitializer<'info> for InitializeSolend<'info> {
 pub struct RefreshSolend<'info> {
     /// vault state account
     /// checks that the accounts passed in are correct
-    #[account(mut, has_one = vault_solend_lp_token, has_one = solend_reserve)]
+    #[account(mut)]
     pub vault: Box<Account<'info, Vault>>,
 
     /// Token account for the vault's solend lp tokens
@@ -415,6 +250,8 @@ pub struct RefreshSolend<'info> {
     pub clock: Sysvar<'info, Clock>,
 }
 
+impl_has_vault!(RefreshSolend<'_>);
+
 impl<'info> RefreshSolend<'info> {
     fn solend_refresh_reserve_context(
         &self,
@@ -437,6 +274,12 @@ impl<'info> Refresher<'info> for RefreshSolend<'info> {
         &mut self,
         _remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
+        let index = self.vault.yield_source_index(
+            Provider::Solend,
+            self.solend_reserve.key(),
+            self.vault_solend_lp_token.key(),
+        )?;
+
         #[cfg(feature = "debug")]
         msg!("Refreshing solend");
 
@@ -449,7 +292,9 @@ impl<'info> Refresher<'info> for RefreshSolend<'info> {
         #[cfg(feature = "debug")]
         msg!("Value: {}", solend_value);
 
-        self.vault.actual_allocations[Provider::Solend].update(solend_value, self.clock.slot);
+        self.vault.yield_sources[index]
+            .actual_allocation
+            .update(solend_value, &self.clock);
 
         Ok(())
     }
@@ -0,0 +1,496 @@
+use std::ops::{Deref, DerefMut};
+
+use anchor_lang::{prelude::*, solana_program, solana_program::program_pack::Pack};
+use anchor_spl::token::{Token, TokenAccount};
+use boolinator::Boolinator;
+use solana_maths::Rate;
+use spl_token_lending::state::Reserve;
+
+use crate::{
+    errors::ErrorCode,
+    impl_has_vault,
+    init_yield_source::YieldSourceInitializer,
+    reconcile::LendingMarket,
+    refresh::Refresher,
+    reserves::{Provider, ReserveAccessor},
+    state::Vault,
+};
+
+/// Decodes a reserve of a token-lending fork
+/// Checks that the reserve is owned by the given lending program
+pub fn unpack_token_lending_reserve(
+    lending_program: &AccountInfo,
+    reserve: &AccountInfo,
+) -> Result<Reserve> {
+    (reserve.owner == lending_program.key).ok_or(ErrorCode::InvalidAccount)?;
+
+    match Reserve::unpack(&reserve.try_borrow_data()?) {
+        Ok(val) => Ok(val),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Checks that the lending program passed in is the one the yield source was registered with
+fn check_lending_program(
+    vault: &Vault,
+    index: usize,
+    lending_program: &AccountInfo,
+) -> Result<()> {
+    (vault.yield_sources[index].lending_program == lending_program.key())
+        .ok_or_else(|| ErrorCode::InvalidAccount.into())
+}
+
+#[derive(Accounts)]
+pub struct TokenLendingAccounts<'info> {
+    /// Vault state account
+    /// Checks that the accounts passed in are correct
+    #[account(mut, has_one = vault_authority, has_one = vault_reserve_token)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Authority that the vault uses for lp token mints/burns ans transfers to/from downstream assets
+    pub vault_authority: AccountInfo<'info>,
+
+    /// Token account for the vault's reserve tokens
+    #[account(mut)]
+    pub vault_reserve_token: Box<Account<'info, TokenAccount>>,
+
+    /// Token account for the vault's token-lending lp tokens
+    #[account(mut)]
+    pub vault_token_lending_lp_token: Box<Account<'info, TokenAccount>>,
+
+    /// Program of the token-lending fork
+    /// Checked against the program the yield source was registered with
+    #[account(executable)]
+    pub token_lending_program: AccountInfo<'info>,
+
+    pub token_lending_market_authority: AccountInfo<'info>,
+
+    pub token_lending_market: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub token_lending_reserve: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub token_lending_lp_mint: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub token_lending_reserve_token: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl_has_vault!(TokenLendingAccounts<'_>);
+
+impl<'info> TokenLendingAccounts<'info> {
+    fn reserve(&self) -> Result<Reserve> {
+        unpack_token_lending_reserve(&self.token_lending_program, &self.token_lending_reserve)
+    }
+}
+
+impl<'info> LendingMarket for TokenLendingAccounts<'info> {
+    fn deposit(&self, amount: u64) -> Result<()> {
+        let context = CpiContext::new(
+            self.token_lending_program.clone(),
+            DepositReserveLiquidity {
+                lending_program: self.token_lending_program.clone(),
+                source_liquidity: self.vault_reserve_token.to_account_info(),
+                destination_collateral_account: self
+                    .vault_token_lending_lp_token
+                    .to_account_info(),
+                reserve: self.token_lending_reserve.clone(),
+                reserve_collateral_mint: self.token_lending_lp_mint.clone(),
+                reserve_liquidity_supply: self.token_lending_reserve_token.clone(),
+                lending_market: self.token_lending_market.clone(),
+                lending_market_authority: self.token_lending_market_authority.clone(),
+                transfer_authority: self.vault_authority.clone(),
+                clock: self.clock.to_account_info(),
+                token_program_id: self.token_program.to_account_info(),
+            },
+        );
+
+        match amount {
+            0 => Ok(()),
+            _ => deposit_reserve_liquidity(
+                context.with_signer(&[&self.vault.authority_seeds()]),
+                amount,
+            ),
+        }
+    }
+
+    fn redeem(&self, amount: u64) -> Result<()> {
+        let context = CpiContext::new(
+            self.token_lending_program.clone(),
+            RedeemReserveCollateral {
+                lending_program: self.token_lending_program.clone(),
+                source_collateral: self.vault_token_lending_lp_token.to_account_info(),
+                destination_liquidity: self.vault_reserve_token.to_account_info(),
+                reserve: self.token_lending_reserve.clone(),
+                reserve_collateral_mint: self.token_lending_lp_mint.clone(),
+                reserve_liquidity_supply: self.token_lending_reserve_token.clone(),
+                lending_market: self.token_lending_market.clone(),
+                lending_market_authority: self.token_lending_market_authority.clone(),
+                transfer_authority: self.vault_authority.clone(),
+                clock: self.clock.to_account_info(),
+                token_program_id: self.token_program.to_account_info(),
+            },
+        );
+
+        match amount {
+            0 => Ok(()),
+            _ => redeem_reserve_collateral(
+                context.with_signer(&[&self.vault.authority_seeds()]),
+                amount,
+            ),
+        }
+    }
+
+    fn convert_amount_reserve_to_lp(&self, amount: u64) -> Result<u64> {
+        let exchange_rate = self.reserve()?.collateral_exchange_rate()?;
+        match exchange_rate.liquidity_to_collateral(amount) {
+            Ok(val) => Ok(val),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn convert_amount_lp_to_reserve(&self, amount: u64) -> Result<u64> {
+        let exchange_rate = self.reserve()?.collateral_exchange_rate()?;
+        match exchange_rate.collateral_to_liquidity(amount) {
+            Ok(val) => Ok(val),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn reserve_tokens_in_vault(&self) -> u64 {
+        self.vault_reserve_token.amount
+    }
+
+    fn lp_tokens_in_vault(&self) -> u64 {
+        self.vault_token_lending_lp_token.amount
+    }
+
+    fn provider(&self) -> Provider {
+        Provider::TokenLending
+    }
+
+    fn yield_source_index(&self) -> Result<usize> {
+        let index = self.vault.yield_source_index(
+            Provider::TokenLending,
+            self.token_lending_reserve.key(),
+            self.vault_token_lending_lp_token.key(),
+        )?;
+        check_lending_program(&self.vault, index, &self.token_lending_program)?;
+        Ok(index)
+    }
+}
+
+impl ReserveAccessor for Reserve {
+    fn utilization_rate(&self) -> Result<Rate> {
+        Ok(Rate::from_scaled_val(
+            self.liquidity.utilization_rate()?.to_scaled_val() as u64,
+        ))
+    }
+
+    fn borrow_rate(&self) -> Result<Rate> {
+        Ok(Rate::from_scaled_val(
+            self.current_borrow_rate()?.to_scaled_val() as u64,
+        ))
+    }
+
+    fn reserve_with_deposit(&self, allocation: u64) -> Result<Box<dyn ReserveAccessor>> {
+        let mut reserve = Box::new(self.clone());
+        reserve.liquidity.deposit(allocation)?;
+        Ok(reserve)
+    }
+}
+
+pub fn deposit_reserve_liquidity<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, DepositReserveLiquidity<'info>>,
+    liquidity_amount: u64,
+) -> Result<()> {
+    let ix = spl_token_lending::instruction::deposit_reserve_liquidity(
+        *ctx.accounts.lending_program.key,
+        liquidity_amount,
+        *ctx.accounts.source_liquidity.key,
+        *ctx.accounts.destination_collateral_account.key,
+        *ctx.accounts.reserve.key,
+        *ctx.accounts.reserve_liquidity_supply.key,
+        *ctx.accounts.reserve_collateral_mint.key,
+        *ctx.accounts.lending_market.key,
+        *ctx.accounts.transfer_authority.key,
+    );
+
+    solana_program::program::invoke_signed(
+        &ix,
+        &ToAccountInfos::to_account_infos(&ctx),
+        ctx.signer_seeds,
+    )?;
+
+    Ok(())
+}
+
+pub fn redeem_reserve_collateral<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, RedeemReserveCollateral<'info>>,
+    collateral_amount: u64,
+) -> Result<()> {
+    let ix = spl_token_lending::instruction::redeem_reserve_collateral(
+        *ctx.accounts.lending_program.key,
+        collateral_amount,
+        *ctx.accounts.source_collateral.key,
+        *ctx.accounts.destination_liquidity.key,
+        *ctx.accounts.reserve.key,
+        *ctx.accounts.reserve_collateral_mint.key,
+        *ctx.accounts.reserve_liquidity_supply.key,
+        *ctx.accounts.lending_market.key,
+        *ctx.accounts.transfer_authority.key,
+    );
+
+    solana_program::program::invoke_signed(
+        &ix,
+        &ToAccountInfos::to_account_infos(&ctx),
+        ctx.signer_seeds,
+    )?;
+
+    Ok(())
+}
+
+pub fn refresh_reserve<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, RefreshReserve<'info>>,
+) -> Result<()> {
+    let ix = spl_token_lending::instruction::refresh_reserve(
+        *ctx.accounts.lending_program.key,
+        *ctx.accounts.reserve.key,
+        *ctx.accounts.pyth_reserve_liquidity_oracle.key,
+        *ctx.accounts.switchboard_reserve_liquidity_oracle.key,
+    );
+    solana_program::program::invoke_signed(
+        &ix,
+        &ToAccountInfos::to_account_infos(&ctx),
+        ctx.signer_seeds,
+    )?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct DepositReserveLiquidity<'info> {
+    // Lending program
+    pub lending_program: AccountInfo<'info>,
+
+    // Token account for asset to deposit into reserve
+    pub source_liquidity: AccountInfo<'info>,
+
+    // Token account for reserve collateral token
+    pub destination_collateral_account: AccountInfo<'info>,
+
+    // Reserve state account
+    pub reserve: AccountInfo<'info>,
+
+    // Token mint for reserve collateral token
+    pub reserve_collateral_mint: AccountInfo<'info>,
+
+    // Reserve liquidity supply SPL token account
+    pub reserve_liquidity_supply: AccountInfo<'info>,
+
+    // Lending market
+    pub lending_market: AccountInfo<'info>,
+
+    // Lending market Authority (PDA)
+    pub lending_market_authority: AccountInfo<'info>,
+
+    // Transfer auhtority for accounts 1 and 2
+    pub transfer_authority: AccountInfo<'info>,
+
+    // Clock
+    pub clock: AccountInfo<'info>,
+
+    // Token program ID
+    pub token_program_id: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemReserveCollateral<'info> {
+    // Lending program
+    pub lending_program: AccountInfo<'info>,
+
+    // Source token account for reserve collateral token
+    pub source_collateral: AccountInfo<'info>,
+
+    // Destination liquidity token account
+    pub destination_liquidity: AccountInfo<'info>,
+
+    // Refreshed reserve account
+    pub reserve: AccountInfo<'info>,
+
+    // Reserve collateral mint account
+    pub reserve_collateral_mint: AccountInfo<'info>,
+
+    // Reserve liquidity supply SPL Token account.
+    pub reserve_liquidity_supply: AccountInfo<'info>,
+
+    // Lending market
+    pub lending_market: AccountInfo<'info>,
+
+    // Lending market account - PDA
+    pub lending_market_authority: AccountInfo<'info>,
+
+    // User transfer authority
+    pub transfer_authority: AccountInfo<'info>,
+
+    // Clock
+    pub clock: AccountInfo<'info>,
+
+    // Token program ID
+    pub token_program_id: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshReserve<'info> {
+    // Lending program
+    pub lending_program: AccountInfo<'info>,
+
+    // Reserve account
+    pub reserve: AccountInfo<'info>,
+
+    // Pyth reserve liquidity oracle
+    // Must be the pyth price account specified in InitReserve
+    pub pyth_reserve_liquidity_oracle: AccountInfo<'info>,
+
+    // Switchboard Reserve liquidity oracle account
+    // Must be the switchboard price account specified in InitReserve
+    pub switchboard_reserve_liquidity_oracle: AccountInfo<'info>,
+
+    // Clock
+    pub clock: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(bump: u8)]
+pub struct InitializeTokenLending<'info> {
+    #[account(mut, has_one = owner, has_one = vault_authority)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    pub vault_authority: AccountInfo<'info>,
+
+    /// Token account for the vault's token-lending lp tokens
+    #[account(init, payer = payer, seeds = [vault.key().as_ref(), token_lending_lp_token_mint.key().as_ref()], bump, token::authority = vault_authority, token::mint = token_lending_lp_token_mint)]
+    pub vault_token_lending_lp_token: Box<Account<'info, TokenAccount>>,
+
+    pub token_lending_lp_token_mint: AccountInfo<'info>,
+
+    /// Program of the token-lending fork
+    /// Recorded on the yield source so later instructions can only use this program
+    #[account(executable)]
+    pub token_lending_program: AccountInfo<'info>,
+
+    pub token_lending_reserve: AccountInfo<'info>,
+
+    pub owner: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+
+    pub rent: Sysvar<'info, Rent>,
+}
+
+impl<'info> YieldSourceInitializer<'info> for InitializeTokenLending<'info> {
+    fn initialize_yield_source(&mut self) -> Result<()> {
+        // Make sure that the reserve is a token-lending reserve for the lp token being registered
+        let reserve =
+            unpack_token_lending_reserve(&self.token_lending_program, &self.token_lending_reserve)?;
+        (reserve.collateral.mint_pubkey == self.token_lending_lp_token_mint.key())
+            .ok_or(ErrorCode::InvalidAccount)?;
+
+        self.vault.add_yield_source(
+            Provider::TokenLending,
+            self.token_lending_program.key(),
+            self.token_lending_reserve.key(),
+            self.vault_token_lending_lp_token.key(),
+        )?;
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct RefreshTokenLending<'info> {
+    /// Vault state account
+    /// Checks that the accounts passed in are correct
+    #[account(mut)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Token account for the vault's token-lending lp tokens
+    pub vault_token_lending_lp_token: Box<Account<'info, TokenAccount>>,
+
+    /// Program of the token-lending fork
+    /// Checked against the program the yield source was registered with
+    #[account(executable)]
+    pub token_lending_program: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub token_lending_reserve: AccountInfo<'info>,
+
+    pub token_lending_pyth: AccountInfo<'info>,
+
+    pub token_lending_switchboard: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+impl_has_vault!(RefreshTokenLending<'_>);
+
+impl<'info> RefreshTokenLending<'info> {
+    fn token_lending_refresh_reserve_context(
+        &self,
+    ) -> CpiContext<'_, '_, '_, 'info, RefreshReserve<'info>> {
+        CpiContext::new(
+            self.token_lending_program.clone(),
+            RefreshReserve {
+                lending_program: self.token_lending_program.clone(),
+                reserve: self.token_lending_reserve.clone(),
+                pyth_reserve_liquidity_oracle: self.token_lending_pyth.clone(),
+                switchboard_reserve_liquidity_oracle: self.token_lending_switchboard.clone(),
+                clock: self.clock.to_account_info(),
+            },
+        )
+    }
+}
+
+impl<'info> Refresher<'info> for RefreshTokenLending<'info> {
+    fn update_actual_allocation(
+        &mut self,
+        _remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let index = self.vault.yield_source_index(
+            Provider::TokenLending,
+            self.token_lending_reserve.key(),
+            self.vault_token_lending_lp_token.key(),
+        )?;
+        check_lending_program(&self.vault, index, &self.token_lending_program)?;
+
+        #[cfg(feature = "debug")]
+        msg!("Refreshing token-lending reserve");
+
+        refresh_reserve(self.token_lending_refresh_reserve_context())?;
+
+        // Reserve has to be decoded after the refresh to pick up the accrued interest
+        let reserve =
+            unpack_token_lending_reserve(&self.token_lending_program, &self.token_lending_reserve)?;
+        let token_lending_value = reserve
+            .collateral_exchange_rate()?
+            .collateral_to_liquidity(self.vault_token_lending_lp_token.amount)?;
+
+        #[cfg(feature = "debug")]
+        msg!("Value: {}", token_lending_value);
+
+        self.vault.yield_sources[index]
+            .actual_allocation
+            .update(token_lending_value, &self.clock);
+
+        Ok(())
+    }
+}
@@ -1,77 +1,85 @@
 use core::iter::FromIterator;
 
-use strum::IntoEnumIterator;
-
-use crate::reserves::{Provider, ProviderIter};
-
 use super::AssetContainerGeneric;
 
 pub struct AssetContainerIterator<'inner, T, const N: usize> {
     inner: &'inner AssetContainerGeneric<T, N>,
-    inner_iter: ProviderIter,
+    index: usize,
 }
 
 impl<'inner, T, const N: usize> Iterator for AssetContainerIterator<'inner, T, N> {
-    type Item = (Provider, &'inner T);
+    type Item = (usize, &'inner T);
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner_iter
-            .next()
-            .map(|provider| (provider, &self.inner[provider]))
+        while self.index < N {
+            let index = self.index;
+            self.index += 1;
+
+            // Skip over uninitialized entries
+            if let Some(v) = self.inner.inner[index].as_ref() {
+                return Some((index, v));
+            }
+        }
+        None
     }
 }
 
 pub struct OwnedAssetContainerIterator<T, const N: usize> {
     inner: AssetContainerGeneric<T, N>,
-    inner_iter: ProviderIter,
+    index: usize,
 }
 
-impl<'inner, T, const N: usize> Iterator for OwnedAssetContainerIterator<T, N> {
-    type Item = (Provider, T);
+impl<T, const N: usize> Iterator for OwnedAssetContainerIterator<T, N> {
+    type Item = (usize, T);
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner_iter.next().map(|provider| {
-            (
-                provider,
-                self.inner.inner[provider as usize]
-                    .take()
-                    .expect("missing index in OwnedAssetContainerIterator"),
-            )
-        })
+        while self.index < N {
+            let index = self.index;
+            self.index += 1;
+
+            // Skip over uninitialized entries
+            if let Some(v) = self.inner.inner[index].take() {
+                return Some((index, v));
+            }
+        }
+        None
     }
 }
 
 impl<'a, T, const N: usize> IntoIterator for &'a AssetContainerGeneric<T, N> {
-    type Item = (Provider, &'a T);
+    type Item = (usize, &'a T);
     type IntoIter = AssetContainerIterator<'a, T, N>;
 
     fn into_iter(self) -> Self::IntoIter {
         AssetContainerIterator {
             inner: self,
-            inner_iter: Provider::iter(),
+            index: 0,
         }
     }
 }
 
 impl<T, const N: usize> IntoIterator for AssetContainerGeneric<T, N> {
-    type Item = (Provider, T);
+    type Item = (usize, T);
     type IntoIter = OwnedAssetContainerIterator<T, N>;
 
     fn into_iter(self) -> Self::IntoIter {
         OwnedAssetContainerIterator {
             inner: self,
-            inner_iter: Provider::iter(),
+            index: 0,
         }
     }
 }
 
-// Allows us to create a AssetContainerGeneric<T, N> from an Iterator that yields (Provider, T)
-impl<T: Default, const N: usize> FromIterator<(Provider, T)> for AssetContainerGeneric<T, N> {
-    fn from_iter<U: IntoIterator<Item = (Provider, T)>>(iter: U) -> Self {
+// Allows us to create a AssetContainerGeneric<T, N> from an Iterator that yields (usize, T)
+// Indices that are not yielded are left uninitialized
+impl<T, const N: usize> FromIterator<(usize, T)> for AssetContainerGeneric<T, N> {
+    fn from_iter<U: IntoIterator<Item = (usize, T)>>(iter: U) -> Self {
         iter.into_iter().fold(
-            AssetContainerGeneric::default(),
-            |mut acc, (provider, v)| {
-                acc[provider] = v;
+            AssetContainerGeneric {
+                inner: [(); N].map(|_| None),
+            },
+            |mut acc, (index, v)| {
+                acc.inner[index] = Some(v);
                 acc
             },
         )
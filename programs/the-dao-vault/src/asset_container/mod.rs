@@ -10,50 +10,59 @@ pub use self::u64::*;
 
 use core::ops::{Index, IndexMut};
 
-use strum::{EnumCount, IntoEnumIterator};
+use anchor_lang::prelude::*;
+use boolinator::Boolinator;
 
-use crate::reserves::Provider;
+use crate::{errors::ErrorCode, state::MAX_YIELD_SOURCES};
 
-pub type AssetContainer<T> = AssetContainerGeneric<T, { Provider::COUNT }>;
+pub type AssetContainer<T> = AssetContainerGeneric<T, MAX_YIELD_SOURCES>;
 
-/// Provides an abstraction over supported assets
+/// Provides an abstraction over the yield sources of a vault
+/// Entries are indexed by yield source index, unused slots are None
 #[derive(Debug, Clone)]
 pub struct AssetContainerGeneric<T, const N: usize> {
     pub(crate) inner: [Option<T>; N],
 }
 
 impl<T, const N: usize> AssetContainerGeneric<T, N> {
+    /// Returns the number of initialized entries
     pub fn len(&self) -> usize {
-        N
+        self.inner.iter().filter(|v| v.is_some()).count()
     }
 
     /// Returns if the container is uninitialized
     pub fn is_empty(&self) -> bool {
         self.inner.iter().all(Option::is_none)
     }
+
+    /// Returns the entry at the given index if it is initialized
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.inner.get(index).and_then(Option::as_ref)
+    }
+
+    /// Returns an error unless weights capped at `allocation_cap_pct`
+    /// can add up to 100% over the initialized entries
+    pub fn verify_allocation_cap(&self, allocation_cap_pct: u8) -> Result<()> {
+        (self.len() * allocation_cap_pct as usize >= 100)
+            .ok_or_else(|| ErrorCode::InvalidAloocationCap.into())
+    }
 }
 
-impl<T, const N: usize> Index<Provider> for AssetContainerGeneric<T, N> {
+impl<T, const N: usize> Index<usize> for AssetContainerGeneric<T, N> {
     type Output = T;
 
-    fn index(&self, index: Provider) -> &Self::Output {
-        self.inner[index as usize].as_ref().unwrap_or_else(|| {
-            panic!(
-                "missing index {:?} / {:?} in AssetContainerGeneric",
-                index, index as usize
-            )
-        })
+    fn index(&self, index: usize) -> &Self::Output {
+        self.inner[index]
+            .as_ref()
+            .unwrap_or_else(|| panic!("missing index {:?} in AssetContainerGeneric", index))
     }
 }
 
-impl<T, const N: usize> IndexMut<Provider> for AssetContainerGeneric<T, N> {
-    fn index_mut(&mut self, index: Provider) -> &mut Self::Output {
-        self.inner[index as usize].as_mut().unwrap_or_else(|| {
-            panic!(
-                "missing index {:?} / {:?} in AssetContainerGeneric",
-                index, index as usize
-            )
-        })
+impl<T, const N: usize> IndexMut<usize> for AssetContainerGeneric<T, N> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        self.inner[index]
+            .as_mut()
+            .unwrap_or_else(|| panic!("missing index {:?} in AssetContainerGeneric", index))
     }
 }
 
@@ -65,55 +74,52 @@ impl<T: Default, const N: usize> Default for AssetContainerGeneric<T, N> {
     }
 }
 
-impl<'a, T, const N: usize> From<&'a dyn Index<Provider, Output = &'a T>>
-    for AssetContainerGeneric<&'a T, N>
-where
-    &'a T: Default,
-{
-    fn from(p: &'a dyn Index<Provider, Output = &'a T>) -> Self {
-        Provider::iter().fold(AssetContainerGeneric::default(), |mut acc, provider| {
-            acc[provider] = p[provider];
-            acc
-        })
-    }
-}
-
 impl<T, const N: usize> AssetContainerGeneric<T, N> {
-    pub fn apply_owned<U: Clone + Default, F: Fn(Provider, T) -> U>(
-        mut self,
-        f: F,
-    ) -> AssetContainerGeneric<U, N> {
-        Provider::iter()
-            .map(|provider| {
-                (
-                    provider,
-                    f(
-                        provider,
-                        self.inner[provider as usize]
-                            .take()
-                            .expect("unable to take() in apply_owned()"),
-                    ),
-                )
-            })
+    pub fn apply_owned<U, F: Fn(usize, T) -> U>(self, f: F) -> AssetContainerGeneric<U, N> {
+        self.into_iter()
+            .map(|(index, v)| (index, f(index, v)))
             .collect()
     }
 
     /// Applies 'f' to each element of the container individually, yielding a new container
-    pub fn apply<U: Default, F: Fn(Provider, &T) -> U>(&self, f: F) -> AssetContainerGeneric<U, N> {
-        // Because we have FromIterator<(Provider, T)> if we yield a tuple of
-        // (Provider, U) we can collect() this into a AssetContainerGeneric<U>
-        Provider::iter()
-            .map(|provider| (provider, f(provider, &self[provider])))
+    pub fn apply<U, F: Fn(usize, &T) -> U>(&self, f: F) -> AssetContainerGeneric<U, N> {
+        // Because we have FromIterator<(usize, T)> if we yield a tuple of
+        // (usize, U) we can collect() this into a AssetContainerGeneric<U>
+        self.into_iter()
+            .map(|(index, v)| (index, f(index, v)))
             .collect()
     }
 
     /// Identical to 'apply' but returns a Result<AssetContainerGeneric<..>>
-    pub fn try_apply<U: Default, E, F: Fn(Provider, &T) -> Result<U, E>>(
+    pub fn try_apply<U, E, F: Fn(usize, &T) -> Result<U, E>>(
         &self,
         f: F,
     ) -> Result<AssetContainerGeneric<U, N>, E> {
-        Provider::iter()
-            .map(|provider| f(provider, &self[provider]).map(|res| (provider, res)))
+        self.into_iter()
+            .map(|(index, v)| f(index, v).map(|res| (index, res)))
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_allocation_cap() {
+        let mut assets = AssetContainerGeneric::<u64, 8> { inner: [None; 8] };
+        assets.inner[0] = Some(0);
+        assets.inner[3] = Some(0);
+
+        assert!(assets.verify_allocation_cap(50).is_ok());
+        // The lowest cap allowed for 8 yield sources leaves most of the vault unallocated
+        // when only 2 of them are enabled
+        assert!(assets.verify_allocation_cap(13).is_err());
+
+        let assets = AssetContainerGeneric::<u64, 8> {
+            inner: [Some(0); 8],
+        };
+        assert!(assets.verify_allocation_cap(13).is_ok());
+        assert!(assets.verify_allocation_cap(12).is_err());
+    }
+}
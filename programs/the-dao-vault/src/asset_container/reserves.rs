@@ -8,7 +8,7 @@ use anchor_lang::prelude::*;
 
 use crate::{
     errors::ErrorCode,
-    reserves::{Provider, Reserves, ReturnCalculator},
+    reserves::{Reserves, ReturnCalculator},
     state::StrategyType,
 };
 
@@ -26,10 +26,10 @@ impl AssetContainer<Reserves> {
             })
             .try_fold(
                 (AssetContainer::<Rate>::default(), Rate::one()),
-                |(mut strategy_weights, remaining_weight), (provider, _)| {
+                |(mut strategy_weights, remaining_weight), (index, _)| {
                     let target_weight =
                         remaining_weight.min(Rate::from_percent(allocation_cap_pct));
-                    strategy_weights[provider] = target_weight;
+                    strategy_weights[index] = target_weight;
                     match remaining_weight.try_sub(target_weight) {
                         Ok(r) => Ok((strategy_weights, r)),
                         Err(e) => Err(e.into()),
@@ -61,15 +61,20 @@ impl AssetContainer<Reserves> {
         allocation_cap_pct: u8,
     ) -> Result<AssetContainer<Rate>> {
         match strategy_type {
-            StrategyType::MaxYield => self.calculate_weights_max_yield(allocation_cap_pct),
+            StrategyType::MaxYield => {
+                // The cap is validated against the max number of yield sources when it is set,
+                // but fewer may be enabled, in which case capped weights would not add up to 100%
+                self.verify_allocation_cap(allocation_cap_pct)?;
+                self.calculate_weights_max_yield(allocation_cap_pct)
+            }
             StrategyType::EqualAllocation => self.calculate_weight_equal(),
         }
     }
 
     pub fn get_apr(
         &self,
-        weights: &dyn Index<Provider, Output = Rate>,
-        allocations: &dyn Index<Provider, Output = u64>,
+        weights: &dyn Index<usize, Output = Rate>,
+        allocations: &dyn Index<usize, Output = u64>,
     ) -> Result<Rate> {
         let res = self
             .into_iter()
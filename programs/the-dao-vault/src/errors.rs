@@ -49,4 +49,64 @@ pub enum ErrorCode {
 
     #[msg("Vault is halted")]
     HaltedVault,
+
+    #[msg("Redeem is not followed by a matching withdraw or fee collection in the same transaction")]
+    WithdrawInstructionNotFound,
+
+    #[msg("Signer is not the pending owner of the vault")]
+    InvalidPendingOwner,
+
+    #[msg("Yield source is already registered with the vault")]
+    YieldSourceAlreadyRegistered,
+
+    #[msg("Vault has reached the maximum number of yield sources")]
+    YieldSourceLimitReached,
+
+    #[msg("Deposit is too small to mint any lp tokens")]
+    ZeroLpTokensMinted,
+
+    #[msg("Deposit would mint fewer lp tokens than the minimum requested")]
+    InsufficientLpTokensOut,
+
+    #[msg("Withdraw would transfer fewer reserve tokens than the minimum requested")]
+    InsufficientReserveTokensOut,
+
+    #[msg("Transaction landed after the maximum slot requested")]
+    TransactionExpired,
+
+    #[msg("Fees have not been collected in the current slot")]
+    FeesNotCollected,
+
+    #[msg("Fee recipients must be distinct with non-zero shares that add up to 100%")]
+    InvalidFeeRecipients,
+
+    #[msg("Deposits cannot name their own user or a referrer other than the position's as the referrer")]
+    InvalidReferrer,
+
+    #[msg("User is not in the deposit allowlist")]
+    DepositNotAllowlisted,
+
+    #[msg("Deposit would take the user over their allowlisted deposit limit")]
+    UserDepositLimitReached,
+
+    #[msg("Withdrawal request has no lp tokens")]
+    EmptyWithdrawalRequest,
+
+    #[msg("Withdrawal requests have to be fulfilled in the order they were made")]
+    WithdrawalQueueOutOfOrder,
+
+    #[msg("Vault does not hold enough reserve tokens to fulfil the withdrawal request")]
+    InsufficientVaultLiquidity,
+
+    #[msg("Liquidity buffer cannot be set to over 100%")]
+    InvalidBufferConfig,
+
+    #[msg("Jet reserve has not been refreshed in the current slot")]
+    JetReserveIsStale,
+
+    #[msg("Withdrawal request is not waiting in the queue")]
+    WithdrawalRequestNotQueued,
+
+    #[msg("Withdrawal request has no reserve tokens to claim")]
+    WithdrawalRequestNotClaimable,
 }
@@ -0,0 +1,38 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::ErrorCode, state::Vault};
+
+#[event]
+pub struct OwnerChangedEvent {
+    vault: Pubkey,
+    old_owner: Pubkey,
+    new_owner: Pubkey,
+}
+
+#[derive(Accounts)]
+pub struct AcceptOwner<'info> {
+    /// Vault state account
+    /// Checks that the signer is the proposed owner
+    #[account(mut, constraint = vault.pending_owner == pending_owner.key() @ ErrorCode::InvalidPendingOwner)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Account proposed as the new owner
+    /// Must be signer so that ownership cannot move to an account nobody controls
+    pub pending_owner: Signer<'info>,
+}
+
+/// Completes a vault ownership transfer started by propose_owner
+pub fn handler(ctx: Context<AcceptOwner>) -> Result<()> {
+    let vault = &mut ctx.accounts.vault;
+    let old_owner = vault.owner;
+
+    vault.owner = vault.pending_owner;
+    vault.pending_owner = Pubkey::default();
+
+    emit!(OwnerChangedEvent {
+        vault: vault.key(),
+        old_owner,
+        new_owner: vault.owner,
+    });
+    Ok(())
+}
@@ -0,0 +1,211 @@
+use boolinator::Boolinator;
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount, Transfer};
+
+use crate::{
+    errors::ErrorCode,
+    math::calc_reserve_to_lp,
+    state::{
+        UserPosition, Vault, VaultFlags, WithdrawalPricePolicy, WithdrawalRequest,
+        WithdrawalRequestStatus,
+    },
+};
+
+#[event]
+pub struct WithdrawalRequestCancelledEvent {
+    vault: Pubkey,
+    user: Pubkey,
+    index: u64,
+    /// Lp tokens returned to the user
+    lp_token_amount: u64,
+}
+
+#[derive(Accounts)]
+pub struct CancelWithdrawalRequest<'info> {
+    /// Vault state account
+    /// Checks that refresh has been called in the same slot
+    /// Checks that the accounts passed in are correct
+    #[account(
+        mut,
+        constraint = !vault.value.last_update.is_stale(clock.slot)? @ ErrorCode::VaultIsNotRefreshed,
+        has_one = lp_token_mint,
+        has_one = vault_authority,
+    )]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Authority that the vault uses for lp token mints/burns and transfers to/from downstream assets
+    pub vault_authority: AccountInfo<'info>,
+
+    /// Mint for the vault's lp token
+    #[account(mut)]
+    pub lp_token_mint: Box<Account<'info, Mint>>,
+
+    /// Token account that escrows the lp tokens of withdrawal requests priced at fulfilment
+    #[account(mut, seeds = [vault.key().as_ref(), b"withdrawal_queue".as_ref()], bump)]
+    pub withdrawal_queue_lp_token: Box<Account<'info, TokenAccount>>,
+
+    /// Request that is still waiting in the withdrawal queue
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = withdrawal_request.vault == vault.key() @ ErrorCode::InvalidAccount,
+        constraint = withdrawal_request.status == WithdrawalRequestStatus::Queued
+            && withdrawal_request.index >= vault.withdrawal_queue_head
+            @ ErrorCode::WithdrawalRequestNotQueued,
+    )]
+    pub withdrawal_request: Box<Account<'info, WithdrawalRequest>>,
+
+    /// Token account where the lp tokens are returned to
+    #[account(mut, constraint = user_lp_token.mint == vault.lp_token_mint @ ErrorCode::InvalidAccount)]
+    pub user_lp_token: Box<Account<'info, TokenAccount>>,
+
+    /// User that made the request
+    /// Must be signer
+    pub owner: Signer<'info>,
+
+    /// Position of the owner in the vault, restored once it has been initialized
+    #[account(
+        mut,
+        seeds = [vault.key().as_ref(), b"position".as_ref(), owner.key().as_ref()],
+        bump,
+    )]
+    pub user_position: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub clock: Sysvar<'info, Clock>,
+    // Remaining accounts must hold the referrer account of the user position's referrer
+    // when the request released referred value
+}
+
+impl<'info> CancelWithdrawalRequest<'info> {
+    /// CpiContext for minting vault lp tokens to user account
+    fn mint_to_context(&self) -> CpiContext<'_, '_, '_, 'info, MintTo<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            MintTo {
+                mint: self.lp_token_mint.to_account_info(),
+                to: self.user_lp_token.to_account_info(),
+                authority: self.vault_authority.clone(),
+            },
+        )
+    }
+
+    /// CpiContext for returning escrowed vault lp tokens to user account
+    fn transfer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Transfer {
+                from: self.withdrawal_queue_lp_token.to_account_info(),
+                to: self.user_lp_token.to_account_info(),
+                authority: self.vault_authority.clone(),
+            },
+        )
+    }
+}
+
+/// Cancels a withdrawal request that is still waiting in the queue
+///
+/// Escrowed lp tokens are returned as they are, while the reserve tokens locked in at request
+/// time go back into the vault value and are minted as lp tokens at the current share price,
+/// up to the lp tokens that the request burned
+/// If the share price fell since the request, the value above them stays with the vault,
+/// so that cancelling never buys back more lp tokens than were given up
+/// No withdrawal fee was charged at request time, so there is none to refund
+/// The user position gets back the tracked lp tokens and the referred value
+/// that the request took off it
+/// The request keeps its place until it reaches the head of the queue, where it is closed
+pub fn handler(ctx: Context<CancelWithdrawalRequest>) -> Result<()> {
+    // Check that withdraws are not halted
+    (!ctx
+        .accounts
+        .vault
+        .flags()
+        .contains(VaultFlags::HALT_DEPOSITS_WITHDRAWS))
+    .ok_or(ErrorCode::HaltedVault)?;
+
+    let vault = &ctx.accounts.vault;
+    let request = &ctx.accounts.withdrawal_request;
+    let index = request.index;
+
+    #[cfg(feature = "debug")]
+    msg!("Cancelling withdrawal request {}", index);
+
+    let lp_token_amount = match request.price_policy {
+        WithdrawalPricePolicy::AtRequest => {
+            let lp_token_amount = calc_reserve_to_lp(
+                request.reserve_token_amount,
+                ctx.accounts.lp_token_mint.supply,
+                vault.unlocked_value(ctx.accounts.clock.slot)?,
+            )
+            .ok_or(ErrorCode::MathError)?
+            .min(request.lp_token_amount);
+
+            token::mint_to(
+                ctx.accounts
+                    .mint_to_context()
+                    .with_signer(&[&vault.authority_seeds()]),
+                lp_token_amount,
+            )?;
+
+            let reserve_token_amount = request.reserve_token_amount;
+            let vault = &mut ctx.accounts.vault;
+            vault.queued_withdrawal_value = vault
+                .queued_withdrawal_value
+                .checked_sub(reserve_token_amount)
+                .ok_or(ErrorCode::MathError)?;
+            vault.value.value = vault
+                .value
+                .value
+                .checked_add(reserve_token_amount)
+                .ok_or(ErrorCode::OverflowError)?;
+
+            lp_token_amount
+        }
+        WithdrawalPricePolicy::AtFulfil => {
+            let lp_token_amount = request.lp_token_amount;
+            token::transfer(
+                ctx.accounts
+                    .transfer_context()
+                    .with_signer(&[&vault.authority_seeds()]),
+                lp_token_amount,
+            )?;
+
+            let vault = &mut ctx.accounts.vault;
+            vault.queued_lp_tokens = vault
+                .queued_lp_tokens
+                .checked_sub(lp_token_amount)
+                .ok_or(ErrorCode::MathError)?;
+
+            lp_token_amount
+        }
+    };
+
+    let request = &ctx.accounts.withdrawal_request;
+    let (position_lp_tokens, released_referred_value) =
+        (request.position_lp_tokens, request.released_referred_value);
+    if position_lp_tokens > 0 || released_referred_value > 0 {
+        let mut user_position = UserPosition::if_initialized(&ctx.accounts.user_position)?
+            .ok_or(ErrorCode::InvalidAccount)?;
+        let vault_key = ctx.accounts.vault.key();
+        ctx.accounts.vault.restore_referred_value(
+            &vault_key,
+            &mut user_position,
+            ctx.remaining_accounts,
+            released_referred_value,
+        )?;
+        user_position.record_cancelled_withdraw(position_lp_tokens.min(lp_token_amount))?;
+        user_position.exit(&crate::ID)?;
+    }
+
+    ctx.accounts.withdrawal_request.status = WithdrawalRequestStatus::Cancelled;
+
+    emit!(WithdrawalRequestCancelledEvent {
+        vault: ctx.accounts.vault.key(),
+        user: ctx.accounts.owner.key(),
+        index,
+        lp_token_amount,
+    });
+    Ok(())
+}
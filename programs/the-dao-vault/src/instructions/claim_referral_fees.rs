@@ -0,0 +1,125 @@
+use boolinator::Boolinator;
+
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::get_associated_token_address,
+    token::{self, Mint, Token, TokenAccount, Transfer},
+};
+
+use crate::{
+    errors::ErrorCode,
+    state::{FeeDenomination, Referrer, Vault, VaultFlags},
+};
+
+#[event]
+pub struct ReferralFeesClaimedEvent {
+    vault: Pubkey,
+    referrer: Pubkey,
+    fee_denomination: FeeDenomination,
+    amount: u64,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferralFees<'info> {
+    /// Vault state account
+    /// Checks that the accounts passed in are correct
+    #[account(has_one = vault_authority)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Authority that the vault uses for lp token mints/burns and transfers to/from downstream assets
+    pub vault_authority: AccountInfo<'info>,
+
+    /// Referrer account of the owner in the vault
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [vault.key().as_ref(), b"referrer".as_ref(), owner.key().as_ref()],
+        bump,
+    )]
+    pub referrer: Box<Account<'info, Referrer>>,
+
+    /// Owner of the referrer account
+    /// Must be signer
+    pub owner: Signer<'info>,
+
+    /// Mint of the fee denomination that is claimed
+    pub fee_mint: Box<Account<'info, Mint>>,
+
+    /// Associated token account of the vault authority that holds the unclaimed referral fees
+    #[account(
+        mut,
+        address = get_associated_token_address(&vault_authority.key(), &fee_mint.key()),
+    )]
+    pub referral_fee_escrow: Box<Account<'info, TokenAccount>>,
+
+    /// Token account where the referral fees are transfered to
+    #[account(mut, constraint = receiver_token.mint == fee_mint.key() @ ErrorCode::InvalidAccount)]
+    pub receiver_token: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> ClaimReferralFees<'info> {
+    /// CpiContext for transfering referral fees from the escrow to the receiver
+    fn transfer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Transfer {
+                from: self.referral_fee_escrow.to_account_info(),
+                to: self.receiver_token.to_account_info(),
+                authority: self.vault_authority.clone(),
+            },
+        )
+    }
+
+    /// Fee denomination that the fee mint belongs to
+    fn fee_denomination(&self) -> Result<FeeDenomination> {
+        let fee_mint = self.fee_mint.key();
+        if fee_mint == self.vault.lp_token_mint {
+            Ok(FeeDenomination::LpToken)
+        } else {
+            (fee_mint == self.vault.reserve_token_mint)
+                .as_result(FeeDenomination::ReserveToken, ErrorCode::InvalidAccount.into())
+        }
+    }
+}
+
+/// Claims the referral fees that a referrer was paid in the fee mint's denomination
+///
+/// Referral fees accrue in proportion to the referred value at every fee collection
+/// and are held by the vault authority until claimed
+pub fn handler(ctx: Context<ClaimReferralFees>) -> Result<()> {
+    // Check that withdraws are not halted
+    (!ctx
+        .accounts
+        .vault
+        .flags()
+        .contains(VaultFlags::HALT_DEPOSITS_WITHDRAWS))
+    .ok_or(ErrorCode::HaltedVault)?;
+
+    let fee_denomination = ctx.accounts.fee_denomination()?;
+
+    ctx.accounts.referrer.settle(&ctx.accounts.vault)?;
+    let amount =
+        std::mem::take(&mut ctx.accounts.referrer.unclaimed_fees[fee_denomination as usize]);
+
+    #[cfg(feature = "debug")]
+    msg!("Claiming {} referral fees paid as {:?}", amount, fee_denomination);
+
+    if amount > 0 {
+        token::transfer(
+            ctx.accounts
+                .transfer_context()
+                .with_signer(&[&ctx.accounts.vault.authority_seeds()]),
+            amount,
+        )?;
+    }
+
+    emit!(ReferralFeesClaimedEvent {
+        vault: ctx.accounts.vault.key(),
+        referrer: ctx.accounts.owner.key(),
+        fee_denomination,
+        amount,
+    });
+    Ok(())
+}
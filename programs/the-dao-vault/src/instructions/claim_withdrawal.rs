@@ -0,0 +1,109 @@
+use boolinator::Boolinator;
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+
+use crate::{
+    errors::ErrorCode,
+    state::{Vault, VaultFlags, WithdrawalRequest, WithdrawalRequestStatus},
+};
+
+#[event]
+pub struct WithdrawalClaimedEvent {
+    vault: Pubkey,
+    user: Pubkey,
+    index: u64,
+    amount: u64,
+}
+
+#[derive(Accounts)]
+pub struct ClaimWithdrawal<'info> {
+    /// Vault state account
+    /// Checks that the accounts passed in are correct
+    #[account(has_one = vault_authority)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Authority that the vault uses for lp token mints/burns and transfers to/from downstream assets
+    pub vault_authority: AccountInfo<'info>,
+
+    /// Token account that holds the reserve tokens of fulfilled withdrawal requests
+    /// until their owners claim them
+    #[account(mut, seeds = [vault.key().as_ref(), b"withdrawal_queue_reserve".as_ref()], bump)]
+    pub withdrawal_queue_reserve_token: Box<Account<'info, TokenAccount>>,
+
+    /// Fulfilled request that holds reserve tokens for its owner
+    /// Closed to its owner once claimed
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner,
+        constraint = withdrawal_request.vault == vault.key() @ ErrorCode::InvalidAccount,
+        constraint = withdrawal_request.status == WithdrawalRequestStatus::Claimable
+            @ ErrorCode::WithdrawalRequestNotClaimable,
+    )]
+    pub withdrawal_request: Box<Account<'info, WithdrawalRequest>>,
+
+    /// User that made the request
+    /// Must be signer
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// Token account where the reserve tokens are transfered to
+    #[account(mut, constraint = receiver_token.mint == vault.reserve_token_mint @ ErrorCode::InvalidAccount)]
+    pub receiver_token: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> ClaimWithdrawal<'info> {
+    /// CpiContext for transfering reserve tokens from the queue escrow to the receiver
+    fn transfer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Transfer {
+                from: self.withdrawal_queue_reserve_token.to_account_info(),
+                to: self.receiver_token.to_account_info(),
+                authority: self.vault_authority.clone(),
+            },
+        )
+    }
+}
+
+/// Claims the reserve tokens of a fulfilled withdrawal request
+/// that the owner's associated reserve token account could not receive
+pub fn handler(ctx: Context<ClaimWithdrawal>) -> Result<()> {
+    // Check that withdraws are not halted
+    (!ctx
+        .accounts
+        .vault
+        .flags()
+        .contains(VaultFlags::HALT_DEPOSITS_WITHDRAWS))
+    .ok_or(ErrorCode::HaltedVault)?;
+
+    let request = &ctx.accounts.withdrawal_request;
+    let (index, amount) = (request.index, request.claimable_amount);
+
+    #[cfg(feature = "debug")]
+    msg!(
+        "Claiming {} reserve tokens of withdrawal request {}",
+        amount,
+        index
+    );
+
+    if amount > 0 {
+        token::transfer(
+            ctx.accounts
+                .transfer_context()
+                .with_signer(&[&ctx.accounts.vault.authority_seeds()]),
+            amount,
+        )?;
+    }
+
+    emit!(WithdrawalClaimedEvent {
+        vault: ctx.accounts.vault.key(),
+        user: ctx.accounts.owner.key(),
+        index,
+        amount,
+    });
+    Ok(())
+}
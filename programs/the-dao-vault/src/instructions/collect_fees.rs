@@ -0,0 +1,321 @@
+use boolinator::Boolinator;
+
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::get_associated_token_address,
+    token::{self, Mint, MintTo, Token, TokenAccount, Transfer},
+};
+
+use crate::{
+    errors::ErrorCode,
+    math::{calc_referrer_fees, ONE_AS_BPS},
+    state::{FeeDenomination, Vault, VaultFlags},
+};
+
+#[event]
+pub struct FeeCollectedEvent {
+    vault: Pubkey,
+    /// Vault value that the fees were charged on
+    vault_value: u64,
+    /// Fees denominated in reserve tokens
+    total_fees: u64,
+    /// Token that the fees below were paid in
+    fee_denomination: FeeDenomination,
+    /// Fees paid to each fee recipient in fee recipient order
+    recipient_fees: Vec<u64>,
+    referral_fees: u64,
+    /// Part of the referral fees that went to the referrers
+    referrer_fees: u64,
+    /// Fees cover the slots after `start_slot` up to and including `end_slot`
+    start_slot: u64,
+    end_slot: u64,
+    high_water_mark: u64,
+}
+
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    /// Vault state account
+    /// Checks that consolidate refresh has been called in the same slot
+    /// so that fees are charged on the current vault value
+    /// Checks that the accounts passed in are correct
+    #[account(
+        mut,
+        constraint = vault.value.last_update.slots_elapsed(clock.slot)? == 0 @ ErrorCode::VaultIsNotRefreshed,
+        has_one = vault_authority,
+        has_one = vault_reserve_token,
+        has_one = lp_token_mint,
+    )]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Authority that the vault uses for lp token mints/burns and transfers to/from downstream assets
+    pub vault_authority: AccountInfo<'info>,
+
+    /// Token account for the vault's reserve tokens
+    #[account(mut)]
+    pub vault_reserve_token: Box<Account<'info, TokenAccount>>,
+
+    /// Mint for the vault lp token
+    #[account(mut)]
+    pub lp_token_mint: Box<Account<'info, Mint>>,
+
+    /// Referral fee receiver, or the associated reserve token account of the referral fee owner
+    /// for fees paid in reserve tokens
+    #[account(mut)]
+    pub referral_fee_receiver: AccountInfo<'info>,
+
+    /// Associated token account of the vault authority for the fee denomination
+    /// that holds the referrers' share of the referral fees until they claim it
+    #[account(mut)]
+    pub referral_fee_escrow: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub clock: Sysvar<'info, Clock>,
+    // Remaining accounts are the fee recipient token accounts for the fee denomination,
+    // in fee recipient order
+}
+
+impl<'info> CollectFees<'info> {
+    /// CpiContext for collecting fees by minting new vault lp tokens
+    fn mint_to_context(
+        &self,
+        fee_receiver: AccountInfo<'info>,
+    ) -> CpiContext<'_, '_, '_, 'info, MintTo<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            MintTo {
+                mint: self.lp_token_mint.to_account_info(),
+                to: fee_receiver,
+                authority: self.vault_authority.clone(),
+            },
+        )
+    }
+
+    /// CpiContext for collecting fees by transfering reserve tokens out of the vault
+    fn transfer_context(
+        &self,
+        fee_receiver: AccountInfo<'info>,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Transfer {
+                from: self.vault_reserve_token.to_account_info(),
+                to: fee_receiver,
+                authority: self.vault_authority.clone(),
+            },
+        )
+    }
+
+    /// Mint of the token that fees are paid in
+    fn fee_mint(&self) -> Pubkey {
+        match self.vault.config.fee_denomination {
+            FeeDenomination::LpToken => self.lp_token_mint.key(),
+            FeeDenomination::ReserveToken => self.vault.reserve_token_mint,
+        }
+    }
+
+    /// Token account that receives fees in the fee denomination for the owner of
+    /// the lp token account `lp_token_account`
+    /// Fees paid in reserve tokens go to the owner's associated token account
+    fn fee_receiver(&self, lp_token_account: &Pubkey, owner: &Pubkey) -> Pubkey {
+        match self.vault.config.fee_denomination {
+            FeeDenomination::LpToken => *lp_token_account,
+            FeeDenomination::ReserveToken => {
+                get_associated_token_address(owner, &self.vault.reserve_token_mint)
+            }
+        }
+    }
+
+    /// Pays fees to a receiver in the fee denomination
+    fn pay_fees(
+        &self,
+        fee_receiver: &AccountInfo<'info>,
+        expected_fee_receiver: &Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        (fee_receiver.key() == *expected_fee_receiver).ok_or(ErrorCode::InvalidAccount)?;
+
+        if amount == 0 {
+            return Ok(());
+        }
+
+        #[cfg(feature = "debug")]
+        msg!("Paying {} in fees to {}", amount, expected_fee_receiver);
+
+        match self.vault.config.fee_denomination {
+            FeeDenomination::LpToken => token::mint_to(
+                self.mint_to_context(fee_receiver.clone())
+                    .with_signer(&[&self.vault.authority_seeds()]),
+                amount,
+            ),
+            FeeDenomination::ReserveToken => token::transfer(
+                self.transfer_context(fee_receiver.clone())
+                    .with_signer(&[&self.vault.authority_seeds()]),
+                amount,
+            ),
+        }
+    }
+}
+
+/// Splits the non-referral fees between the fee recipients by their shares
+/// Rounding dust goes to the first fee recipient
+fn split_fees(vault: &Vault, fees: u64) -> Result<Vec<u64>> {
+    let mut split = vault
+        .registered_fee_recipients()
+        .map(|recipient| {
+            (fees as u128)
+                .checked_mul(recipient.share_bps as u128)
+                .map(|n| (n / ONE_AS_BPS as u128) as u64)
+                .ok_or_else(|| ErrorCode::MathError.into())
+        })
+        .collect::<Result<Vec<u64>>>()?;
+
+    let dust = fees
+        .checked_sub(split.iter().sum())
+        .ok_or(ErrorCode::MathError)?;
+    if let Some(first) = split.first_mut() {
+        *first += dust;
+    }
+
+    Ok(split)
+}
+
+/// Crystallizes the management and carry fees accrued since the last collection
+///
+/// Fees are paid to the fee recipients, the referrers and the referral fee receiver, either as
+/// newly minted vault lp tokens or as reserve tokens out of the vault depending on the fee
+/// denomination
+/// Referrers get the part of the referral fees that their referred value makes up of the vault
+/// value, which is held in escrow until they claim it
+/// Paying in reserve tokens needs enough idle reserve tokens in the vault, a reconcile with
+/// a withdraw option can redeem the shortfall before this instruction in the same transaction
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, CollectFees<'info>>) -> Result<()> {
+    #[cfg(feature = "debug")]
+    msg!("Collecting fees");
+
+    // Fees are charged on refreshed values, so they are halted along with refreshes
+    (!ctx.accounts.vault.flags().contains(VaultFlags::HALT_REFRESHED))
+        .ok_or(ErrorCode::HaltedVault)?;
+
+    let clock = &ctx.accounts.clock;
+    let slot = clock.slot;
+    let start_slot = ctx.accounts.vault.last_fee_collection.slot;
+    let vault_value = ctx.accounts.vault.value.value;
+    let fee_denomination = ctx.accounts.vault.config.fee_denomination;
+
+    let (total_fees, mgmt_fee_remainder) = ctx.accounts.vault.calculate_fees(
+        vault_value,
+        ctx.accounts.lp_token_mint.supply,
+        clock.unix_timestamp,
+    )?;
+
+    let total_fees_converted = match fee_denomination {
+        // Fees are part of the vault value, so lp tokens are minted against the value without them
+        FeeDenomination::LpToken => crate::math::calc_reserve_to_lp(
+            total_fees,
+            ctx.accounts.lp_token_mint.supply,
+            vault_value.saturating_sub(total_fees),
+        )
+        .ok_or(ErrorCode::MathError)?,
+        FeeDenomination::ReserveToken => total_fees,
+    };
+
+    #[cfg(feature = "debug")]
+    msg!(
+        "Total fees: {} reserve tokens, {} paid as {:?}",
+        total_fees,
+        total_fees_converted,
+        fee_denomination
+    );
+
+    let referral_fees_converted = total_fees_converted
+        .checked_mul(ctx.accounts.vault.config.referral_fee_pct as u64)
+        .and_then(|val| val.checked_div(100))
+        .ok_or(ErrorCode::MathError)?;
+
+    // Rounding dust goes to the fee recipients
+    let primary_fees_converted = total_fees_converted
+        .checked_sub(referral_fees_converted)
+        .ok_or(ErrorCode::MathError)?;
+
+    let recipient_fees_converted = split_fees(&ctx.accounts.vault, primary_fees_converted)?;
+    let recipients = ctx.accounts.vault.registered_fee_recipients();
+    (ctx.remaining_accounts.len() >= recipient_fees_converted.len())
+        .ok_or(ErrorCode::InsufficientAccounts)?;
+
+    for ((recipient, account), fees_converted) in recipients
+        .zip(ctx.remaining_accounts)
+        .zip(recipient_fees_converted.iter().copied())
+    {
+        let fee_receiver = ctx
+            .accounts
+            .fee_receiver(&recipient.token_account, &recipient.owner);
+        ctx.accounts
+            .pay_fees(account, &fee_receiver, fees_converted)?;
+    }
+
+    // Referrers share the referral fees on the part of the vault value that they referred
+    let referrer_fees_converted = calc_referrer_fees(
+        referral_fees_converted,
+        ctx.accounts.vault.total_referred_value,
+        vault_value,
+    )
+    .ok_or(ErrorCode::MathError)?;
+    let referral_fee_escrow = get_associated_token_address(
+        &ctx.accounts.vault.vault_authority,
+        &ctx.accounts.fee_mint(),
+    );
+    ctx.accounts.pay_fees(
+        &ctx.accounts.referral_fee_escrow,
+        &referral_fee_escrow,
+        referrer_fees_converted,
+    )?;
+    if referrer_fees_converted > 0 {
+        ctx.accounts
+            .vault
+            .accrue_referral_fees(fee_denomination, referrer_fees_converted)?;
+    }
+
+    let referral_fee_receiver = ctx.accounts.fee_receiver(
+        &ctx.accounts.vault.referral_fee_receiver,
+        &ctx.accounts.vault.referral_fee_owner,
+    );
+    ctx.accounts.pay_fees(
+        &ctx.accounts.referral_fee_receiver,
+        &referral_fee_receiver,
+        referral_fees_converted
+            .checked_sub(referrer_fees_converted)
+            .ok_or(ErrorCode::MathError)?,
+    )?;
+
+    // Fees change either the supply or the value that the share price is computed from
+    ctx.accounts.lp_token_mint.reload()?;
+    let vault_value_after_fees = match fee_denomination {
+        FeeDenomination::LpToken => vault_value,
+        FeeDenomination::ReserveToken => vault_value
+            .checked_sub(total_fees)
+            .ok_or(ErrorCode::MathError)?,
+    };
+
+    // Carry is charged up to the current share price, so it becomes the new mark
+    ctx.accounts
+        .vault
+        .update_high_water_mark(vault_value_after_fees, ctx.accounts.lp_token_mint.supply)?;
+    ctx.accounts.vault.value.value = vault_value_after_fees;
+    ctx.accounts.vault.mgmt_fee_remainder = mgmt_fee_remainder;
+    ctx.accounts.vault.last_fee_collection.update(clock);
+
+    emit!(FeeCollectedEvent {
+        vault: ctx.accounts.vault.key(),
+        vault_value,
+        total_fees,
+        fee_denomination,
+        recipient_fees: recipient_fees_converted,
+        referral_fees: referral_fees_converted,
+        referrer_fees: referrer_fees_converted,
+        start_slot,
+        end_slot: slot,
+        high_water_mark: ctx.accounts.vault.high_water_mark,
+    });
+    Ok(())
+}
@@ -1,23 +1,21 @@
-#![allow(dead_code)]
-#![allow(unused_imports)]
-
 use boolinator::Boolinator;
 
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount};
-use port_anchor_adaptor::{port_lending_id, PortReserve};
+use anchor_spl::token::{Mint, Token, TokenAccount};
 
-use crate::adapters::{solend, SolendReserve};
 use crate::errors::ErrorCode;
-use crate::reserves::Provider;
 use crate::state::{Vault, VaultFlags};
-use strum::IntoEnumIterator;
 
 #[derive(Accounts)]
 pub struct ConsolidateRefresh<'info> {
     /// Vault state account
     /// Checks that the accounts passed in are correct
-    #[account(mut, has_one = vault_authority, has_one = vault_reserve_token, has_one = lp_token_mint)]
+    #[account(
+        mut,
+        has_one = vault_authority,
+        has_one = vault_reserve_token,
+        has_one = lp_token_mint,
+    )]
     pub vault: Box<Account<'info, Vault>>,
 
     /// Authority that the vault uses for lp token mints/burns ans transfers to/from downstream assete
@@ -33,45 +31,38 @@ pub struct ConsolidateRefresh<'info> {
     pub token_program: Program<'info, Token>,
 }
 
-impl<'info> ConsolidateRefresh<'info> {
-    /// CpiContext for collecting fees by minting new vault lp tokens
-    #[cfg(feature = "fees")]
-    fn mint_to_context(
-        &self,
-        fee_receiver: &AccountInfo<'info>,
-    ) -> CpiContext<'_, '_, '_, 'info, MintTo<'info>> {
-        CpiContext::new(
-            self.token_program.to_account_info(),
-            MintTo {
-                mint: self.lp_token_mint.to_account_info(),
-                to: fee_receiver.clone(),
-                authority: self.vault_authority.clone(),
-            },
-        )
-    }
-}
-
-/// Updates the vault total value, and collects fees
+/// Updates the vault total value
+///
+/// Gains are locked and released into the share price over the profit unlock slots
+/// Fees are collected separately by collect_fees
 pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, ConsolidateRefresh<'info>>) -> Result<()> {
     #[cfg(feature = "debug")]
-    msg!("Consolidate vault refreshing")
+    msg!("Consolidate vault refreshing");
 
     // Checks that refreshes are not halted
-    (!ctx.accounts.vault.flags().contains(VaultFlags::HALT_REFRESHES))
-    .ok_or::<ProgramError>(ErrorCode::HaltedVault.into());
+    (!ctx.accounts.vault.flags().contains(VaultFlags::HALT_REFRESHED))
+        .ok_or(ErrorCode::HaltedVault)?;
 
-    let clock_slot = Clock::get()?.slot;
+    let clock = Clock::get()?;
     // Calculate new vault value
     let vault_reserve_token_amount = ctx.accounts.vault_reserve_token.amount;
-    let vault_value =
-        Provider::iter().try_fold(ctx.accounts.vault_reserve_token.amount, |acc, p| {
-            let allocation = ctx.accounts.vault.actual_allocations[p];
-            (allocation.last_update.slots_elapsed(clock_slot)? == 0).as_result::<u64, Error>(
+    let vault_value = ctx
+        .accounts
+        .vault
+        .yield_sources
+        .iter()
+        .filter(|yield_source| yield_source.is_registered())
+        .try_fold(vault_reserve_token_amount, |acc, yield_source| {
+            let allocation = yield_source.actual_allocation;
+            (allocation.last_update.slots_elapsed(clock.slot)? == 0).as_result::<u64, Error>(
                 acc.checked_add(allocation.value)
                     .ok_or(ErrorCode::OverflowError)?,
                 ErrorCode::AllocationIsNotUpdated.into(),
             )
-        })?;
+        })?
+        // Reserve tokens owed to queued withdrawal requests no longer belong to lp token holders
+        .checked_sub(ctx.accounts.vault.queued_withdrawal_value)
+        .ok_or(ErrorCode::MathError)?;
 
     #[cfg(feature = "debug")]
     {
@@ -79,87 +70,7 @@ pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, ConsolidateRefresh<'info>>
         msg!("Vault value: {}", vault_value);
     }
 
-    #[cfg(not(feature = "fees"))]
-    if ctx.accounts.vault.config.fee_carry_bps > 0 || ctx.accounts.vault.config.fee_mgmt_bps > 0 {
-        msg!("WARNING: Fees are non-zero but the fee feature is deactivated");
-    }
-
-    #[cfg(feature = "fees")]
-    {
-        let vault = &ctx.accounts.vault;
-
-        // Calculate fees
-        let total_fees = vault.calculate_fees(vault_value, clock_slot)?;
-
-        let total_fees_covered = crate::math::calc_reserve_to_lp(
-            total_fees,
-            ctx.accounts.lp_token_mint.supply,
-            vault.value,
-        )
-        .ok_or(ErrorCode::MathError)?;
-
-        #[cfg(feature = "debug")]
-        msg!(
-            "Total fees: {} reserve tokens, {} lp tokens",
-            total_fees,
-            total_fees_covered
-        );
-
-        let primary_fees_converted = total_fees_covered
-            .checked_mul(100 - ctx.accounts.vault.config.referral_fee_pct as u64)
-            .and_then(|val| val.checked_div(100))
-            .ok_or(ErrorCode::MathError)?;
-
-        let referral_fees_converted = total_fees_covered
-            .checked_mul(ctx.accounts.vault.config.referral_fee_pct as u64)
-            .and_then(|val| val.checked_div(100))
-            .ok_or(ErrorCode::MathError)?;
-
-        #[cfg(feature = "debug")]
-        msg!(
-            "Collecting primary fees: {} lp tokens",
-            primary_fees_converted
-        );
-
-        if ctx.remaining_accounts.len() < 2 {
-            msg!("Not enough accounts passed in to collect fees");
-            return Err(ErrorCode::InsufficientAccounts.into());
-        }
-
-        let primary_fee_receiver = &ctx.remaining_accounts[0];
-        if primary_fee_receiver.key() != ctx.accounts.vault.fee_receiver {
-            msg!("Fee receivers do not match");
-            return Err(Errorcode::InvalidAccount.into());
-        }
-
-        token::mint_to(
-            ctx.accounts
-                .mint_to_context(primary_fee_receiver)
-                .with_signer(&[&[vault.authority_seeds]]),
-            primary_fees_converted,
-        )?;
-
-        #[cfg(feature = "debug")]
-        msg!(
-            "Collecting referral fees: {} lp tokens",
-            referral_fees_converted
-        );
-
-        let referral_fee_receiver = &ctx.remaining_accounts[1];
-        if referral_fee_receiver.key() != ctx.accounts.vault.referral_fee_receiver {
-            msg!("Referral fee receivers do not match");
-            return Err(ErrorCode::InvalidAccount.into());
-        }
-
-        token::mint_to(
-            ctx.accounts
-                .mint_to_context(referral_fee_receiver)
-                .with_signer(&[&[vault.authority_seeds()]]),
-            referral_fees_converted,
-        )?;
-    }
-
-    ctx.accounts.vault.value.update(vault_value, clock_slot);
+    ctx.accounts.vault.update_value(vault_value, &clock)?;
 
     Ok(())
 }
@@ -7,7 +7,7 @@ use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount, Transfer};
 
 use crate::{
     errors::ErrorCode,
-    state::{Vault, VaultFlags},
+    state::{Referrer, UserPosition, Vault, VaultFlags},
 };
 
 #[event]
@@ -15,6 +15,15 @@ pub struct DepositEvent {
     vault: Pubkey,
     user: Pubkey,
     amount: u64,
+    referrer: Option<Pubkey>,
+}
+
+#[derive(AnchorDeserialize, AnchorSerialize, Clone, Debug)]
+pub struct AllowlistProofArg {
+    /// Most reserve tokens that the user can deposit in total
+    pub max_deposit: u64,
+    /// Merkle proof of the (user, max deposit) pair in the deposit allowlist
+    pub proof: Vec<[u8; 32]>,
 }
 
 #[derive(Accounts)]
@@ -48,9 +57,18 @@ pub struct Deposit<'info> {
     /// Must be signer
     pub user_authority: Signer<'info>,
 
+    /// Position of the user authority in the vault, updated once it has been initialized
+    #[account(
+        mut,
+        seeds = [vault.key().as_ref(), b"position".as_ref(), user_authority.key().as_ref()],
+        bump,
+    )]
+    pub user_position: AccountInfo<'info>,
+
     pub token_program: Program<'info, Token>,
 
     pub clock: Sysvar<'info, Clock>,
+    // Remaining accounts must hold the referrer account when a referrer is named
 }
 
 impl<'info> Deposit<'info> {
@@ -83,7 +101,19 @@ impl<'info> Deposit<'info> {
 /// Deposit to the vault
 ///
 /// Transfers reserve tokens from user to vault and mints their share of lp tokens
-pub fn handler(ctx: Context<Deposit>, reserve_token_amount: u64) -> Result<()> {
+/// Fails if fewer than `min_lp_out` lp tokens would be minted or if the slot is past `max_slot`
+/// Records the deposit in the user position once it has been initialized,
+/// which is required while the deposit allowlist is enabled or a referrer is named
+/// Credits the deposit to `referrer` if one is named, which has to stay the same for the position
+/// While the deposit allowlist is enabled, `allowlist_proof` has to prove the user's max deposit
+pub fn handler(
+    ctx: Context<Deposit>,
+    reserve_token_amount: u64,
+    min_lp_out: u64,
+    max_slot: u64,
+    referrer: Option<Pubkey>,
+    allowlist_proof: Option<AllowlistProofArg>,
+) -> Result<()> {
     #[cfg(feature = "debug")]
     msg!("Depositting {} reserve tokens", reserve_token_amount);
 
@@ -95,15 +125,23 @@ pub fn handler(ctx: Context<Deposit>, reserve_token_amount: u64) -> Result<()> {
         .contains(VaultFlags::HALT_DEPOSITS_WITHDRAWS))
     .ok_or(ErrorCode::HaltedVault)?;
 
+    (ctx.accounts.clock.slot <= max_slot).ok_or(ErrorCode::TransactionExpired)?;
+
     let vault = &ctx.accounts.vault;
 
+    let mut user_position = UserPosition::if_initialized(&ctx.accounts.user_position)?;
+
     let lp_tokens_to_mint = crate::math::calc_reserve_to_lp(
         reserve_token_amount,
         ctx.accounts.lp_token_mint.supply,
-        vault.value.value,
+        vault.unlocked_value(ctx.accounts.clock.slot)?,
     )
     .ok_or(ErrorCode::MathError)?;
 
+    // Rounding down to zero would hand the deposit to the existing lp token holders
+    (lp_tokens_to_mint > 0).ok_or(ErrorCode::ZeroLpTokensMinted)?;
+    (lp_tokens_to_mint >= min_lp_out).ok_or(ErrorCode::InsufficientLpTokensOut)?;
+
     let total_value = ctx
         .accounts
         .vault
@@ -117,6 +155,27 @@ pub fn handler(ctx: Context<Deposit>, reserve_token_amount: u64) -> Result<()> {
         return Err(ErrorCode::DepositCapError.into());
     }
 
+    if vault.deposit_allowlist_enabled {
+        let allowlist_proof = allowlist_proof.ok_or(ErrorCode::DepositNotAllowlisted)?;
+        vault
+            .is_deposit_allowlisted(
+                &ctx.accounts.user_authority.key(),
+                allowlist_proof.max_deposit,
+                &allowlist_proof.proof,
+            )
+            .ok_or(ErrorCode::DepositNotAllowlisted)?;
+
+        // The user position keeps the cumulative deposits that the limit applies to
+        let total_deposited = user_position
+            .as_ref()
+            .ok_or(ErrorCode::InsufficientAccounts)?
+            .total_deposited
+            .checked_add(reserve_token_amount)
+            .ok_or(ErrorCode::OverflowError)?;
+        (total_deposited <= allowlist_proof.max_deposit)
+            .ok_or(ErrorCode::UserDepositLimitReached)?;
+    }
+
     token::transfer(ctx.accounts.transfer_context(), reserve_token_amount)?;
 
     #[cfg(feature = "debug")]
@@ -138,10 +197,38 @@ pub fn handler(ctx: Context<Deposit>, reserve_token_amount: u64) -> Result<()> {
         .checked_add(reserve_token_amount)
         .ok_or(ErrorCode::MathError)?;
 
+    if let Some(referrer) = referrer {
+        (referrer != ctx.accounts.user_authority.key()).ok_or(ErrorCode::InvalidReferrer)?;
+
+        // The position remembers the referrer so that withdraws can take the value back off it
+        let user_position = user_position
+            .as_mut()
+            .ok_or(ErrorCode::InsufficientAccounts)?;
+        user_position.record_referral(&referrer, reserve_token_amount)?;
+
+        let mut referrer_account =
+            Referrer::from_accounts(ctx.remaining_accounts, &ctx.accounts.vault.key(), &referrer)?
+                .ok_or(ErrorCode::InsufficientAccounts)?;
+        ctx.accounts
+            .vault
+            .add_referred_value(&mut referrer_account, reserve_token_amount)?;
+        referrer_account.exit(&crate::ID)?;
+    }
+
+    if let Some(user_position) = user_position.as_mut() {
+        user_position.record_deposit(
+            reserve_token_amount,
+            lp_tokens_to_mint,
+            ctx.accounts.clock.slot,
+        )?;
+        user_position.exit(&crate::ID)?;
+    }
+
     emit!(DepositEvent {
         vault: ctx.accounts.vault.key(),
         user: ctx.accounts.user_authority.key(),
         amount: reserve_token_amount,
+        referrer,
     });
     Ok(())
 }
@@ -0,0 +1,284 @@
+use boolinator::Boolinator;
+
+use anchor_lang::{prelude::*, AccountsClose};
+use anchor_spl::{
+    associated_token::get_associated_token_address,
+    token::{self, Burn, Mint, Token, TokenAccount, Transfer},
+};
+
+use crate::{
+    errors::ErrorCode,
+    math::{calc_lp_to_reserve, calc_withdrawal_fee},
+    state::{
+        UserPosition, Vault, VaultFlags, WithdrawalFeeDestination, WithdrawalPricePolicy,
+        WithdrawalRequest, WithdrawalRequestStatus,
+    },
+};
+
+#[event]
+pub struct WithdrawalFulfilledEvent {
+    vault: Pubkey,
+    user: Pubkey,
+    index: u64,
+    amount: u64,
+    withdrawal_fee: u64,
+    /// Whether the amount was left on the request for the user to claim
+    claimable: bool,
+}
+
+#[derive(Accounts)]
+pub struct FulfilWithdrawal<'info> {
+    /// Vault state account
+    /// Checks that refresh has been called in the same slot
+    /// Checks that the accounts passed in are correct
+    #[account(
+        mut,
+        constraint = !vault.value.last_update.is_stale(clock.slot)? @ ErrorCode::VaultIsNotRefreshed,
+        has_one = lp_token_mint,
+        has_one = vault_authority,
+        has_one = vault_reserve_token,
+    )]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Authority that the vault uses for lp token mints/burns and transfers to/from downstream assets
+    pub vault_authority: AccountInfo<'info>,
+
+    /// Token account for the vault's reserve tokens
+    #[account(mut)]
+    pub vault_reserve_token: Box<Account<'info, TokenAccount>>,
+
+    /// Mint for the vault's lp token
+    #[account(mut)]
+    pub lp_token_mint: Box<Account<'info, Mint>>,
+
+    /// Token account that escrows the lp tokens of withdrawal requests priced at fulfilment
+    #[account(mut, seeds = [vault.key().as_ref(), b"withdrawal_queue".as_ref()], bump)]
+    pub withdrawal_queue_lp_token: Box<Account<'info, TokenAccount>>,
+
+    /// Token account that holds the reserve tokens of fulfilled withdrawal requests
+    /// until their owners claim them
+    #[account(mut, seeds = [vault.key().as_ref(), b"withdrawal_queue_reserve".as_ref()], bump)]
+    pub withdrawal_queue_reserve_token: Box<Account<'info, TokenAccount>>,
+
+    /// Request at the head of the withdrawal queue
+    /// Closed to its owner once paid out or if it was cancelled
+    #[account(
+        mut,
+        has_one = owner,
+        constraint = withdrawal_request.vault == vault.key() @ ErrorCode::InvalidAccount,
+        constraint = withdrawal_request.index == vault.withdrawal_queue_head @ ErrorCode::WithdrawalQueueOutOfOrder,
+    )]
+    pub withdrawal_request: Box<Account<'info, WithdrawalRequest>>,
+
+    /// User that made the request
+    #[account(mut)]
+    pub owner: AccountInfo<'info>,
+
+    /// Associated reserve token account of the owner
+    /// The reserve tokens are left claimable on the request when it cannot receive them
+    #[account(
+        mut,
+        address = get_associated_token_address(&owner.key(), &vault.reserve_token_mint),
+    )]
+    pub owner_reserve_token: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub clock: Sysvar<'info, Clock>,
+    // Remaining accounts can hold the user position of the owner
+    // and, when withdrawal fees go to the fee receiver, the associated reserve token account
+    // of the first fee recipient
+}
+
+impl<'info> FulfilWithdrawal<'info> {
+    /// CpiContext for burning escrowed vault lp tokens
+    fn burn_context(&self) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Burn {
+                mint: self.lp_token_mint.to_account_info(),
+                to: self.withdrawal_queue_lp_token.to_account_info(),
+                authority: self.vault_authority.clone(),
+            },
+        )
+    }
+
+    /// CpiContext for transfering reserve tokens from vault to any token account
+    fn transfer_context(
+        &self,
+        to: AccountInfo<'info>,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Transfer {
+                from: self.vault_reserve_token.to_account_info(),
+                to,
+                authority: self.vault_authority.clone(),
+            },
+        )
+    }
+}
+
+/// Fulfils the withdrawal request at the head of the queue
+///
+/// Anyone can fulfil requests once the vault holds enough reserve tokens, a reconcile
+/// with a withdraw option can redeem them before this instruction in the same transaction
+/// If the owner's associated reserve token account cannot receive the reserve tokens,
+/// they move to the queue's reserve token escrow for the owner to claim
+/// so that the request does not hold up the queue
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, FulfilWithdrawal<'info>>) -> Result<()> {
+    // Check that withdraws are not halted
+    (!ctx
+        .accounts
+        .vault
+        .flags()
+        .contains(VaultFlags::HALT_DEPOSITS_WITHDRAWS))
+    .ok_or(ErrorCode::HaltedVault)?;
+
+    let vault = &ctx.accounts.vault;
+    let request = &ctx.accounts.withdrawal_request;
+    let (index, owner, lp_token_amount) = (request.index, request.owner, request.lp_token_amount);
+
+    #[cfg(feature = "debug")]
+    msg!("Fulfilling withdrawal request {}", index);
+
+    // Cancelled requests only held their place in the queue
+    if request.status == WithdrawalRequestStatus::Cancelled {
+        ctx.accounts.vault.withdrawal_queue_head = index + 1;
+        return ctx
+            .accounts
+            .withdrawal_request
+            .close(ctx.accounts.owner.clone());
+    }
+
+    let reserve_tokens_withdrawn = match request.price_policy {
+        WithdrawalPricePolicy::AtRequest => request.reserve_token_amount,
+        WithdrawalPricePolicy::AtFulfil => calc_lp_to_reserve(
+            request.lp_token_amount,
+            ctx.accounts.lp_token_mint.supply,
+            vault.unlocked_value(ctx.accounts.clock.slot)?,
+        )
+        .ok_or(ErrorCode::MathError)?,
+    };
+
+    let withdrawal_fee = calc_withdrawal_fee(
+        reserve_tokens_withdrawn,
+        request.lp_token_amount,
+        request.decayed_lp_tokens,
+        request.withdrawal_fee_bps,
+        request.decayed_withdrawal_fee_bps,
+    )
+    .ok_or(ErrorCode::MathError)?;
+    let reserve_tokens_to_transfer = reserve_tokens_withdrawn
+        .checked_sub(withdrawal_fee)
+        .ok_or(ErrorCode::MathError)?;
+
+    let fees_to_transfer = match vault.config.withdrawal_fee_destination {
+        WithdrawalFeeDestination::Vault => 0,
+        WithdrawalFeeDestination::FeeReceiver => withdrawal_fee,
+    };
+    (ctx.accounts.vault_reserve_token.amount >= reserve_tokens_to_transfer + fees_to_transfer)
+        .ok_or(ErrorCode::InsufficientVaultLiquidity)?;
+
+    // A closed, uninitialized or frozen token account must not hold up the queue
+    let owner_reserve_token = Account::<TokenAccount>::try_from(&ctx.accounts.owner_reserve_token);
+    let claimable = owner_reserve_token.map_or(true, |token_account| token_account.is_frozen());
+    let destination = if claimable {
+        ctx.accounts
+            .withdrawal_queue_reserve_token
+            .to_account_info()
+    } else {
+        ctx.accounts.owner_reserve_token.clone()
+    };
+
+    token::transfer(
+        ctx.accounts
+            .transfer_context(destination)
+            .with_signer(&[&vault.authority_seeds()]),
+        reserve_tokens_to_transfer,
+    )?;
+
+    if fees_to_transfer > 0 {
+        let fee_receiver_address = vault.withdrawal_fee_receiver();
+        let fee_receiver = ctx
+            .remaining_accounts
+            .iter()
+            .find(|account| account.key() == fee_receiver_address)
+            .ok_or(ErrorCode::InsufficientAccounts)?;
+
+        token::transfer(
+            ctx.accounts
+                .transfer_context(fee_receiver.clone())
+                .with_signer(&[&vault.authority_seeds()]),
+            fees_to_transfer,
+        )?;
+    }
+
+    // Fees left in the vault raise the share price of the remaining lp token holders
+    let reserve_tokens_out = reserve_tokens_to_transfer + fees_to_transfer;
+
+    match request.price_policy {
+        WithdrawalPricePolicy::AtRequest => {
+            // The locked amount was already taken out of the vault value at request time
+            let vault = &mut ctx.accounts.vault;
+            vault.queued_withdrawal_value = vault
+                .queued_withdrawal_value
+                .checked_sub(reserve_tokens_withdrawn)
+                .ok_or(ErrorCode::MathError)?;
+            vault.value.value = vault
+                .value
+                .value
+                .checked_add(reserve_tokens_withdrawn - reserve_tokens_out)
+                .ok_or(ErrorCode::OverflowError)?;
+        }
+        WithdrawalPricePolicy::AtFulfil => {
+            token::burn(
+                ctx.accounts
+                    .burn_context()
+                    .with_signer(&[&vault.authority_seeds()]),
+                lp_token_amount,
+            )?;
+
+            let vault = &mut ctx.accounts.vault;
+            vault.queued_lp_tokens = vault
+                .queued_lp_tokens
+                .checked_sub(lp_token_amount)
+                .ok_or(ErrorCode::MathError)?;
+            vault.value.value = vault
+                .value
+                .value
+                .checked_sub(reserve_tokens_out)
+                .ok_or(ErrorCode::MathError)?;
+        }
+    }
+
+    ctx.accounts.vault.withdrawal_queue_head = index + 1;
+
+    if let Some(mut user_position) =
+        UserPosition::from_accounts(ctx.remaining_accounts, &ctx.accounts.vault.key(), &owner)?
+    {
+        // The lp tokens and the value they referred left the position at request time
+        user_position.record_withdraw(reserve_tokens_to_transfer, 0)?;
+        user_position.exit(&crate::ID)?;
+    }
+
+    if claimable {
+        let request = &mut ctx.accounts.withdrawal_request;
+        request.status = WithdrawalRequestStatus::Claimable;
+        request.claimable_amount = reserve_tokens_to_transfer;
+    } else {
+        ctx.accounts
+            .withdrawal_request
+            .close(ctx.accounts.owner.clone())?;
+    }
+
+    emit!(WithdrawalFulfilledEvent {
+        vault: ctx.accounts.vault.key(),
+        user: owner,
+        index,
+        amount: reserve_tokens_to_transfer,
+        withdrawal_fee,
+        claimable,
+    });
+    Ok(())
+}
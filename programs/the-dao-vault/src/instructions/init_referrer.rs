@@ -0,0 +1,39 @@
+use anchor_lang::prelude::*;
+
+use crate::state::{Referrer, Vault};
+
+#[derive(Accounts)]
+pub struct InitReferrer<'info> {
+    /// Vault state account
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Referrer account of the owner in the vault
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Referrer::LEN,
+        seeds = [vault.key().as_ref(), b"referrer".as_ref(), owner.key().as_ref()],
+        bump,
+    )]
+    pub referrer: Box<Account<'info, Referrer>>,
+
+    /// Account that deposits name as their referrer
+    pub owner: Signer<'info>,
+
+    /// Account that pays for the referrer init
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Creates a referrer account that deposits can name
+///
+/// The referrer only shares in referral fees collected after its first referred deposit
+pub fn handler(ctx: Context<InitReferrer>) -> Result<()> {
+    let referrer = &mut ctx.accounts.referrer;
+    referrer.vault = ctx.accounts.vault.key();
+    referrer.owner = ctx.accounts.owner.key();
+    referrer.referral_fees_per_value = ctx.accounts.vault.referral_fees_per_value;
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use anchor_lang::prelude::*;
+
+use crate::state::{UserPosition, Vault};
+
+#[derive(Accounts)]
+pub struct InitUserPosition<'info> {
+    /// Vault state account
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Position of the owner in the vault
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + UserPosition::LEN,
+        seeds = [vault.key().as_ref(), b"position".as_ref(), owner.key().as_ref()],
+        bump,
+    )]
+    pub user_position: Box<Account<'info, UserPosition>>,
+
+    /// User that the position tracks
+    pub owner: Signer<'info>,
+
+    /// Account that pays for the position init
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Creates an empty position for a user
+///
+/// Deposits and withdraws update the position from then on
+pub fn handler(ctx: Context<InitUserPosition>) -> Result<()> {
+    let user_position = &mut ctx.accounts.user_position;
+    user_position.vault = ctx.accounts.vault.key();
+    user_position.owner = ctx.accounts.owner.key();
+    Ok(())
+}
@@ -6,7 +6,10 @@ use anchor_spl::{
 
 use std::convert::Into;
 
-use crate::state::*;
+use crate::{
+    math::{LP_DECIMALS_OFFSET, ONE_AS_BPS},
+    state::*,
+};
 
 #[derive(AnchorDeserialize, AnchorSerialize, Debug, Clone)]
 pub struct InitBumpSeeds {
@@ -24,6 +27,13 @@ pub struct VaultConfigArg {
     pub allocation_cap_pct: u8,
     pub rebalance_mode: RebalanceMode,
     pub strategy_type: StrategyType,
+    pub fee_denomination: FeeDenomination,
+    pub withdrawal_fee_bps: u16,
+    pub withdrawal_fee_decay_slots: u32,
+    pub withdrawal_fee_destination: WithdrawalFeeDestination,
+    pub withdrawal_price_policy: WithdrawalPricePolicy,
+    pub buffer_pct: u8,
+    pub profit_unlock_slots: u64,
 }
 
 #[derive(Accounts)]
@@ -42,13 +52,14 @@ pub struct Initialize<'info> {
     pub vault_authority: AccountInfo<'info>,
 
     /// Mint for vault lp token
+    /// Its extra decimals limit the vault value to MAX_VAULT_VALUE reserve tokens
     #[account(
         init,
         payer = payer,
         seeds = [vault.key().as_ref(), b"lp_mint".as_ref()],
         bump,
         mint::authority = vault_authority,
-        mint::decimals = reserve_token_mint.decimals,
+        mint::decimals = reserve_token_mint.decimals + LP_DECIMALS_OFFSET,
     )]
     pub lp_token_mint: Box<Account<'info, Mint>>,
 
@@ -68,6 +79,7 @@ pub struct Initialize<'info> {
 
     /// Token account that receives the primary ratio of fees from the vault
     /// denominated in vault lp tokens
+    /// Starts as the only fee recipient until the owner sets others
     #[account(mut)]
     pub fee_receiver: AccountInfo<'info>,
 
@@ -78,6 +90,16 @@ pub struct Initialize<'info> {
     /// Owner of the referral fee reciever token account
     pub referral_fee_owner: AccountInfo<'info>,
 
+    /// Associated lp token account of the vault authority
+    /// that holds the referrers' share of the fees paid in lp tokens until they claim it
+    #[account(mut)]
+    pub lp_referral_fee_escrow: AccountInfo<'info>,
+
+    /// Associated reserve token account of the vault authority
+    /// that holds the referrers' share of the fees paid in reserve tokens until they claim it
+    #[account(mut)]
+    pub reserve_referral_fee_escrow: AccountInfo<'info>,
+
     /// Account that pays for above account inits
     #[account(mut)]
     pub payer: Signer<'info>,
@@ -101,6 +123,7 @@ impl<'info> Initialize<'info> {
         &self,
         fee_token_account: AccountInfo<'info>,
         token_authority: AccountInfo<'info>,
+        mint: AccountInfo<'info>,
     ) -> CpiContext<'_, '_, '_, 'info, Create<'info>> {
         CpiContext::new(
             self.token_program.to_account_info(),
@@ -108,7 +131,7 @@ impl<'info> Initialize<'info> {
                 payer: self.payer.to_account_info(),
                 associated_token: fee_token_account,
                 authority: token_authority,
-                mint: self.lp_token_mint.to_account_info(),
+                mint,
                 system_program: self.system_program.to_account_info(),
                 rent: self.rent.to_account_info(),
                 token_program: self.token_program.to_account_info(),
@@ -149,24 +172,46 @@ pub fn handler(
     vault.vault_reserve_token = ctx.accounts.vault_reserve_token.key();
     vault.lp_token_mint = ctx.accounts.lp_token_mint.key();
     vault.reserve_token_mint = ctx.accounts.reserve_token_mint.key();
-    vault.fee_receiver = ctx.accounts.fee_receiver.key();
     vault.referral_fee_receiver = ctx.accounts.referral_fee_receiver.key();
+    vault.referral_fee_owner = ctx.accounts.referral_fee_owner.key();
     vault.value = SlotTrackecValue {
         value: 0,
-        last_update: LastUpdate::new(clock.slot),
+        last_update: LastUpdate::new(&clock),
     };
     vault.config = VaultConfig::new(config)?;
+    vault.last_fee_collection = LastUpdate::new(&clock);
+    vault.locked_profit_slot = clock.slot;
+    vault.set_fee_recipients(&[FeeRecipient::new(
+        ctx.accounts.fee_receiver.key(),
+        ctx.accounts.owner.key(),
+        ONE_AS_BPS as u16,
+    )])?;
 
     // Initialize fee receiver account
     associated_token::create(ctx.accounts.init_fee_receiver_create_context(
         ctx.accounts.fee_receiver.to_account_info(),
         ctx.accounts.owner.to_account_info(),
+        ctx.accounts.lp_token_mint.to_account_info(),
     ))?;
 
     // Initialize referral fee receiver account
     associated_token::create(ctx.accounts.init_fee_receiver_create_context(
         ctx.accounts.referral_fee_receiver.to_account_info(),
         ctx.accounts.referral_fee_owner.to_account_info(),
+        ctx.accounts.lp_token_mint.to_account_info(),
+    ))?;
+
+    // Initialize the escrows of the referrers' fees in either fee denomination,
+    // since the denomination can change after referrers sign up
+    associated_token::create(ctx.accounts.init_fee_receiver_create_context(
+        ctx.accounts.lp_referral_fee_escrow.to_account_info(),
+        ctx.accounts.vault_authority.to_account_info(),
+        ctx.accounts.lp_token_mint.to_account_info(),
+    ))?;
+    associated_token::create(ctx.accounts.init_fee_receiver_create_context(
+        ctx.accounts.reserve_referral_fee_escrow.to_account_info(),
+        ctx.accounts.vault_authority.to_account_info(),
+        ctx.accounts.reserve_token_mint.to_account_info(),
     ))?;
     Ok(())
 }
@@ -0,0 +1,59 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+use crate::state::Vault;
+
+#[derive(Accounts)]
+pub struct InitWithdrawalQueue<'info> {
+    /// Vault state account
+    /// Checks that the accounts passed in are correct
+    #[account(has_one = vault_authority, has_one = lp_token_mint, has_one = reserve_token_mint)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Authority that the vault uses for lp token mints/burns and transfers to/from downstream assets
+    pub vault_authority: AccountInfo<'info>,
+
+    /// Mint for the vault lp token
+    pub lp_token_mint: Box<Account<'info, Mint>>,
+
+    /// Mint for the vault reserve token
+    pub reserve_token_mint: Box<Account<'info, Mint>>,
+
+    /// Token account that escrows the lp tokens of withdrawal requests priced at fulfilment
+    #[account(
+        init,
+        payer = payer,
+        seeds = [vault.key().as_ref(), b"withdrawal_queue".as_ref()],
+        bump,
+        token::authority = vault_authority,
+        token::mint = lp_token_mint,
+    )]
+    pub withdrawal_queue_lp_token: Box<Account<'info, TokenAccount>>,
+
+    /// Token account that holds the reserve tokens of fulfilled withdrawal requests
+    /// until their owners claim them
+    #[account(
+        init,
+        payer = payer,
+        seeds = [vault.key().as_ref(), b"withdrawal_queue_reserve".as_ref()],
+        bump,
+        token::authority = vault_authority,
+        token::mint = reserve_token_mint,
+    )]
+    pub withdrawal_queue_reserve_token: Box<Account<'info, TokenAccount>>,
+
+    /// Account that pays for the escrow inits
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// Creates the lp and reserve token escrows of the withdrawal queue
+pub fn handler(_ctx: Context<InitWithdrawalQueue>) -> Result<()> {
+    Ok(())
+}
@@ -1,15 +1,51 @@
+pub mod accept_owner;
+pub mod cancel_withdrawal_request;
+pub mod claim_referral_fees;
+pub mod claim_withdrawal;
+pub mod collect_fees;
 pub mod consolidate_refresh;
 pub mod deposit;
+pub mod fulfil_withdrawal;
+pub mod init_referrer;
+pub mod init_user_position;
 pub mod init_vault;
+pub mod init_withdrawal_queue;
 pub mod init_yield_source;
+pub mod preview;
+pub mod propose_owner;
 pub mod rebalance;
 pub mod reconcile;
 pub mod refresh;
+pub mod request_withdraw;
+pub mod set_deposit_allowlist;
+pub mod set_fee_recipients;
+pub mod set_halt_flags;
+pub mod set_yield_source_enabled;
+pub mod update_config;
+pub mod withdraw;
 
+pub use accept_owner::*;
+pub use cancel_withdrawal_request::*;
+pub use claim_referral_fees::*;
+pub use claim_withdrawal::*;
+pub use collect_fees::*;
 pub use consolidate_refresh::*;
 pub use deposit::*;
+pub use fulfil_withdrawal::*;
+pub use init_referrer::*;
+pub use init_user_position::*;
 pub use init_vault::*;
+pub use init_withdrawal_queue::*;
 pub use init_yield_source::*;
+pub use preview::*;
+pub use propose_owner::*;
 pub use rebalance::*;
 pub use reconcile::*;
 pub use refresh::*;
+pub use request_withdraw::*;
+pub use set_deposit_allowlist::*;
+pub use set_fee_recipients::*;
+pub use set_halt_flags::*;
+pub use set_yield_source_enabled::*;
+pub use update_config::*;
+pub use withdraw::*;
@@ -0,0 +1,210 @@
+use boolinator::Boolinator;
+
+use anchor_lang::{prelude::*, solana_program::program::set_return_data};
+use anchor_spl::token::{Mint, TokenAccount};
+
+use crate::{
+    errors::ErrorCode,
+    instructions::AllowlistProofArg,
+    math::{calc_lp_to_reserve, calc_reserve_to_lp, calc_withdrawal_fee},
+    state::{UserPosition, Vault, VaultFlags},
+};
+
+#[derive(Accounts)]
+pub struct Preview<'info> {
+    /// Vault state account
+    /// Checks that refresh has been called in the same slot
+    /// Checks that the accounts passed in are correct
+    #[account(constraint = !vault.value.last_update.is_stale(clock.slot)? @ ErrorCode::VaultIsNotRefreshed, has_one = lp_token_mint)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Mint for the vault's lp token
+    pub lp_token_mint: Box<Account<'info, Mint>>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct PreviewMaxWithdraw<'info> {
+    /// Vault state account
+    /// Checks that refresh has been called in the same slot
+    /// Checks that the accounts passed in are correct
+    #[account(constraint = !vault.value.last_update.is_stale(clock.slot)? @ ErrorCode::VaultIsNotRefreshed, has_one = lp_token_mint)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Mint for the vault's lp token
+    pub lp_token_mint: Box<Account<'info, Mint>>,
+
+    /// Token account holding the lp tokens that would be burned
+    #[account(constraint = user_lp_token.mint == lp_token_mint.key() @ ErrorCode::InvalidAccount)]
+    pub user_lp_token: Box<Account<'info, TokenAccount>>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+fn return_u64(value: u64) -> Result<()> {
+    #[cfg(feature = "debug")]
+    msg!("Returning {}", value);
+
+    set_return_data(&value.to_le_bytes());
+    Ok(())
+}
+
+/// Returns the user position passed in as the first remaining account, if any
+/// Previews do not write to it, so unlike in deposit and withdraw it can be read-only
+fn user_position_from_accounts<'info>(
+    vault: &Pubkey,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Result<Option<Account<'info, UserPosition>>> {
+    remaining_accounts
+        .first()
+        .map(|account| {
+            let user_position = Account::<UserPosition>::try_from(account)?;
+            (user_position.vault == *vault).ok_or(ErrorCode::InvalidAccount)?;
+            Ok(user_position)
+        })
+        .transpose()
+}
+
+/// Lp tokens are priced against the unlocked vault value, the same as in deposit and withdraw
+fn reserve_to_lp(vault: &Vault, lp_token_mint: &Mint, reserve_token_amount: u64) -> Result<u64> {
+    let unlocked_value = vault.unlocked_value(Clock::get()?.slot)?;
+    calc_reserve_to_lp(reserve_token_amount, lp_token_mint.supply, unlocked_value)
+        .ok_or_else(|| ErrorCode::MathError.into())
+}
+
+fn lp_to_reserve(vault: &Vault, lp_token_mint: &Mint, lp_token_amount: u64) -> Result<u64> {
+    let unlocked_value = vault.unlocked_value(Clock::get()?.slot)?;
+    calc_lp_to_reserve(lp_token_amount, lp_token_mint.supply, unlocked_value)
+        .ok_or_else(|| ErrorCode::MathError.into())
+}
+
+/// Returns the reserve tokens transferred for `lp_token_amount` after the withdrawal fee
+/// The fee is decayed for the lp tokens tracked by the user position, the same as in withdraw
+fn lp_to_reserve_after_fee(
+    vault: &Vault,
+    lp_token_mint: &Mint,
+    lp_token_amount: u64,
+    user_position: Option<&UserPosition>,
+) -> Result<u64> {
+    let reserve_tokens = lp_to_reserve(vault, lp_token_mint, lp_token_amount)?;
+    let (decayed_lp_tokens, decayed_fee_bps) =
+        vault.decayed_withdrawal_fee(user_position, Clock::get()?.slot)?;
+    calc_withdrawal_fee(
+        reserve_tokens,
+        lp_token_amount,
+        decayed_lp_tokens,
+        vault.config.withdrawal_fee_bps as u64,
+        decayed_fee_bps,
+    )
+    .and_then(|fee| reserve_tokens.checked_sub(fee))
+    .ok_or_else(|| ErrorCode::MathError.into())
+}
+
+/// Returns the lp tokens that a deposit of `reserve_token_amount` would mint in this slot
+pub fn preview_deposit(ctx: Context<Preview>, reserve_token_amount: u64) -> Result<()> {
+    let lp_tokens_to_mint = reserve_to_lp(
+        &ctx.accounts.vault,
+        &ctx.accounts.lp_token_mint,
+        reserve_token_amount,
+    )?;
+
+    // Deposits that mint nothing are rejected, so they cannot be previewed either
+    (lp_tokens_to_mint > 0).ok_or(ErrorCode::ZeroLpTokensMinted)?;
+
+    return_u64(lp_tokens_to_mint)
+}
+
+/// Returns the reserve tokens that a withdraw of `lp_token_amount` would transfer in this slot
+/// Pass in the user position to preview the fee of the user's withdraw
+pub fn preview_withdraw(ctx: Context<Preview>, lp_token_amount: u64) -> Result<()> {
+    let user_position =
+        user_position_from_accounts(&ctx.accounts.vault.key(), ctx.remaining_accounts)?;
+
+    return_u64(lp_to_reserve_after_fee(
+        &ctx.accounts.vault,
+        &ctx.accounts.lp_token_mint,
+        lp_token_amount,
+        user_position.as_deref(),
+    )?)
+}
+
+/// Returns the lp tokens that `reserve_token_amount` is worth
+pub fn convert_to_shares(ctx: Context<Preview>, reserve_token_amount: u64) -> Result<()> {
+    return_u64(reserve_to_lp(
+        &ctx.accounts.vault,
+        &ctx.accounts.lp_token_mint,
+        reserve_token_amount,
+    )?)
+}
+
+/// Returns the reserve tokens that `lp_token_amount` is worth
+pub fn convert_to_assets(ctx: Context<Preview>, lp_token_amount: u64) -> Result<()> {
+    return_u64(lp_to_reserve(
+        &ctx.accounts.vault,
+        &ctx.accounts.lp_token_mint,
+        lp_token_amount,
+    )?)
+}
+
+/// Returns the most reserve tokens that can be deposited before reaching the deposit cap
+/// While the deposit allowlist is enabled, this is also limited by what is left of the user's
+/// allowlisted max deposit, which takes the user position and `allowlist_proof`
+/// Returns zero while deposits are halted or if the user cannot deposit
+pub fn max_deposit(
+    ctx: Context<Preview>,
+    allowlist_proof: Option<AllowlistProofArg>,
+) -> Result<()> {
+    let vault = &ctx.accounts.vault;
+    let deposit_cap_room = vault.config.deposit_cap.saturating_sub(vault.value.value);
+
+    let max_reserve_tokens = match vault.flags().contains(VaultFlags::HALT_DEPOSITS_WITHDRAWS) {
+        true => 0,
+        false if vault.deposit_allowlist_enabled => {
+            let user_position = user_position_from_accounts(&vault.key(), ctx.remaining_accounts)?;
+            match (user_position, allowlist_proof) {
+                (Some(user_position), Some(allowlist_proof))
+                    if vault.is_deposit_allowlisted(
+                        &user_position.owner,
+                        allowlist_proof.max_deposit,
+                        &allowlist_proof.proof,
+                    ) =>
+                {
+                    deposit_cap_room.min(
+                        allowlist_proof
+                            .max_deposit
+                            .saturating_sub(user_position.total_deposited),
+                    )
+                }
+                _ => 0,
+            }
+        }
+        false => deposit_cap_room,
+    };
+
+    return_u64(max_reserve_tokens)
+}
+
+/// Returns the most reserve tokens that the holder of `user_lp_token` can withdraw
+/// Pass in the holder's user position to account for their decayed withdrawal fee
+/// Returns zero while withdraws are halted
+pub fn max_withdraw(ctx: Context<PreviewMaxWithdraw>) -> Result<()> {
+    let vault = &ctx.accounts.vault;
+    let user_position = user_position_from_accounts(&vault.key(), ctx.remaining_accounts)?;
+    if let Some(user_position) = user_position.as_ref() {
+        (user_position.owner == ctx.accounts.user_lp_token.owner)
+            .ok_or(ErrorCode::InvalidAccount)?;
+    }
+
+    let max_reserve_tokens = match vault.flags().contains(VaultFlags::HALT_DEPOSITS_WITHDRAWS) {
+        true => 0,
+        false => lp_to_reserve_after_fee(
+            vault,
+            &ctx.accounts.lp_token_mint,
+            ctx.accounts.user_lp_token.amount,
+            user_position.as_deref(),
+        )?,
+    };
+
+    return_u64(max_reserve_tokens)
+}
@@ -0,0 +1,38 @@
+use anchor_lang::prelude::*;
+
+use crate::state::Vault;
+
+#[event]
+pub struct OwnerProposedEvent {
+    vault: Pubkey,
+    owner: Pubkey,
+    pending_owner: Pubkey,
+}
+
+#[derive(Accounts)]
+pub struct ProposeOwner<'info> {
+    /// Vault state account
+    #[account(mut, has_one = owner)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Current owner of the vault
+    pub owner: Signer<'info>,
+}
+
+/// Proposes a new owner for the vault
+///
+/// Ownership only changes once the proposed account calls accept_owner.
+/// Proposing the default pubkey cancels a pending transfer
+pub fn handler(ctx: Context<ProposeOwner>, new_owner: Pubkey) -> Result<()> {
+    #[cfg(feature = "debug")]
+    msg!("Proposing new owner {}", new_owner);
+
+    ctx.accounts.vault.pending_owner = new_owner;
+
+    emit!(OwnerProposedEvent {
+        vault: ctx.accounts.vault.key(),
+        owner: ctx.accounts.owner.key(),
+        pending_owner: new_owner,
+    });
+    Ok(())
+}
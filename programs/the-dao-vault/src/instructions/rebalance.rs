@@ -1,16 +1,9 @@
-use std::{convert::TryFrom, ops::Deref};
-
 use boolinator::Boolinator;
-use strum::IntoEnumIterator;
 
 use anchor_lang::prelude::*;
-use port_anchor_adaptor::PortReserve;
 use solana_maths::Rate;
 
-use crate::{
-    adapters::SolendReserve, asset_container::AssetContainer, errors::ErrorCode,
-    impl_provider_index, reserves::Reserves, state::*,
-};
+use crate::{asset_container::AssetContainer, errors::ErrorCode, reserves::Reserves, state::*};
 
 #[event]
 pub struct RebalanceEvent {
@@ -18,20 +11,19 @@ pub struct RebalanceEvent {
 }
 
 /// Used by the SDK to figure out the order in which reconcile TXs should be sent
+/// Allocations are indexed by yield source index
 #[event]
-#[derive(Default)]
 pub struct RebalanceDataEvent {
-    solend: u64,
-    port: u64,
+    allocations: [u64; MAX_YIELD_SOURCES],
 }
-impl_provider_index!(RebalanceDataEvent, u64);
 
-impl From<&Allocations> for RebalanceDataEvent {
-    fn from(allocations: &Allocations) -> Self {
-        Provider::iter().fold(Self::default(), |mut acc, provider| {
-            acc[provider] = allocations[provider].value;
-            acc
-        })
+impl From<&Vault> for RebalanceDataEvent {
+    fn from(vault: &Vault) -> Self {
+        Self {
+            allocations: vault
+                .yield_sources
+                .map(|yield_source| yield_source.target_allocation.value),
+        }
     }
 }
 
@@ -39,43 +31,44 @@ impl From<&Allocations> for RebalanceDataEvent {
 pub struct Rebalance<'info> {
     /// Vault state account
     /// Checks that the refresh has been called in the small slot
-    /// Chekcs that the accounts passed in are correct
-    #[account(mut, constraint = !vault.value.last_update.is_stale(clock.slot)? @ErrorCode::VaultIsNotRefreshed, has_one = solend_reserve, has_one = port_reserve)]
+    #[account(mut, constraint = !vault.value.last_update.is_stale(clock.slot)? @ErrorCode::VaultIsNotRefreshed)]
     pub vault: Box<Account<'info, Vault>>,
 
-    pub solend_reserve: Box<Account<'info, SolendReserve>>,
-
-    pub port_reserve: Box<Account<'info, PortReserve>>,
-
     pub clock: Sysvar<'info, Clock>,
+    // Remaining accounts are the reserves of the enabled yield sources in yield source order
 }
 
-impl TryFrom<&Rebalance<'_>> for AssetContainer<Reserves> {
-    type Error = ProgramError;
-
-    fn try_from(r: &Rebalance<'_>) -> std::result::Result<AssetContainer<Reserves>, Self::Error> {
-        let solend = Some(Reserves::Solend(r.solend_reserve.deref().deref().clone()));
-        let port = Some(Reserves::Port(r.port_reserve.deref().deref().clone()));
-
-        Ok(AssetContainer {
-            inner: [solend, port],
+/// Deserializes the reserves of the enabled yield sources from the remaining accounts
+fn reserves_from_accounts(
+    vault: &Vault,
+    remaining_accounts: &[AccountInfo],
+) -> Result<AssetContainer<Reserves>> {
+    let mut accounts = remaining_accounts.iter();
+    vault
+        .yield_sources
+        .iter()
+        .enumerate()
+        .filter(|(_, yield_source)| yield_source.is_registered() && yield_source.enabled)
+        .map(|(index, yield_source)| {
+            let account = accounts.next().ok_or(ErrorCode::InsufficientAccounts)?;
+            Reserves::try_from_account(yield_source, account).map(|reserve| (index, reserve))
         })
-    }
+        .collect()
 }
 
 #[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug)]
 pub struct StrategyWeightsArg {
-    solend: u16,
-    port: u16,
+    /// Weights in bps indexed by yield source index
+    weights: [u16; MAX_YIELD_SOURCES],
 }
-impl_provider_index!(StrategyWeightsArg, u16);
 
 impl From<StrategyWeightsArg> for AssetContainer<Rate> {
     fn from(s: StrategyWeightsArg) -> Self {
-        Provider::iter().fold(Self::default(), |mut acc, provider| {
-            acc[provider] = Rate::from_bips(s[provider] as u64);
-            acc
-        })
+        s.weights
+            .iter()
+            .enumerate()
+            .map(|(index, weight)| (index, Rate::from_bips(*weight as u64)))
+            .collect()
     }
 }
 
@@ -83,27 +76,50 @@ pub fn handler(ctx: Context<Rebalance>, proposed_weights_arg: StrategyWeightsArg
     #[cfg(feature = "debug")]
     msg!("Rebalancing");
 
-    let vault_value = ctx.accounts.vault.value.value;
-    let slot = Clock::get()?.slot;
-
-    let assets = Box::new(AssetContainer::try_from(&*ctx.accounts)?);
+    // Rebalance only sets targets for reconciles, so it is halted along with them
+    (!ctx
+        .accounts
+        .vault
+        .flags()
+        .contains(VaultFlags::HALT_RECONCILES))
+    .ok_or(ErrorCode::HaltedVault)?;
+
+    // The liquidity buffer stays idle in the vault so that small withdraws never need a redeem
+    let allocatable_value = ctx
+        .accounts
+        .vault
+        .value
+        .value
+        .checked_sub(ctx.accounts.vault.liquidity_buffer()?)
+        .ok_or(ErrorCode::MathError)?;
+    let clock = Clock::get()?;
+
+    let assets = Box::new(reserves_from_accounts(&ctx.accounts.vault, ctx.remaining_accounts)?);
     let strategy_weights = assets.calculate_weights(
         ctx.accounts.vault.config.strategy_type,
         ctx.accounts.vault.config.allocation_cap_pct,
     )?;
 
-    AssetContainer::<u64>::try_from_weights(&strategy_weights, vault_value).and_then(
+    AssetContainer::<u64>::try_from_weights(&strategy_weights, allocatable_value).and_then(
         |strategy_allocations| match ctx.accounts.vault.config.rebalance_mode {
             RebalanceMode::ProofChecker => {
                 let proposed_weights = AssetContainer::<Rate>::from(proposed_weights_arg);
-                let proposed_allocations = AssetContainer::<u64>::try_from_weights(&strategy_weights, vault_value)?;
+                let proposed_allocations = AssetContainer::<u64>::try_from_weights(&proposed_weights, allocatable_value)?;
 
                 #[cfg(feature = "debug")]
-                msg!("Running as proof checker with proposed weights: {:?}", proposed_weights.innder);
+                msg!("Running as proof checker with proposed weights: {:?}", proposed_weights.inner);
 
                 // Check that proposed weights meet necessary constraints
+                // and that the cap lets them add up to 100% over the enabled yield sources
+                assets.verify_allocation_cap(ctx.accounts.vault.config.allocation_cap_pct)?;
                 proposed_weights.verify_weights(ctx.accounts.vault.config.allocation_cap_pct)?;
-                
+
+                // Check that weights are only proposed for enabled yield sources
+                (&proposed_weights)
+                    .into_iter()
+                    .all(|(index, weight)| assets.get(index).is_some() || *weight == Rate::zero())
+                    .ok_or::<Error>(ErrorCode::InvalidProposedWeights.into())?;
+
                 let proposed_apr = assets.get_apr(&proposed_weights, &proposed_allocations)?;
                 let proof_apr = assets.get_apr(&strategy_weights, &strategy_allocations)?;
 
@@ -122,19 +138,15 @@ pub fn handler(ctx: Context<Rebalance>, proposed_weights_arg: StrategyWeightsArg
                 Ok(strategy_allocations)
             }
         }
-    ).map(|final_allocations_container| {
-        let final_allocations = Allocations::from_container(final_allocations_container, slot);
-
+    ).map(|final_allocations| {
         #[cfg(feature = "debug")]
         msg!("Final allocations {:?}", final_allocations);
 
+        ctx.accounts.vault.set_target_allocations(&final_allocations, &clock);
+
         emit!(RebalanceEvent {
             vault: ctx.accounts.vault.key()
         });
-        emit!(RebalanceDataEvent::from(&final_allocations));
-
-
-
-        ctx.accounts.vault.target_allocations = final_allocations;
+        emit!(RebalanceDataEvent::from(&**ctx.accounts.vault));
     })
 }
@@ -1,16 +1,33 @@
 use std::cmp;
 
-use anchor_lang::prelude::*;
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::Instruction,
+        sysvar::{
+            self,
+            instructions::{load_current_index_checked, load_instruction_at_checked},
+        },
+    },
+    Discriminator,
+};
+use anchor_spl::token::Mint;
 use boolinator::Boolinator;
 
 use crate::{
     errors::ErrorCode,
     reserves::Provider,
-    state::{Vault, VaultFlags},
+    state::{
+        FeeDenomination, Vault, VaultFlags, WithdrawalPricePolicy, WithdrawalRequest,
+        WithdrawalRequestStatus,
+    },
 };
 
 const MAX_SLOTS_SINCE_ALLOC_UPDATE: u64 = 100;
 
+/// Position of the withdrawal request among the accounts of a withdrawal request fulfilment
+const FULFIL_WITHDRAWAL_REQUEST_ACCOUNT: usize = 6;
+
 pub trait LendingMarket {
     fn deposit(&self, amount: u64) -> Result<()>;
     fn redeem(&self, amount: u64) -> Result<()>;
@@ -24,9 +41,13 @@ pub trait LendingMarket {
     fn lp_tokens_in_vault(&self) -> u64;
 
     fn provider(&self) -> Provider;
+
+    /// Index of the vault yield source that these accounts belong to
+    fn yield_source_index(&self) -> Result<usize>;
 }
 
 pub trait HasVault {
+    fn vault_key(&self) -> Pubkey;
     fn vault(&self) -> &Vault;
     fn vault_mut(&mut self) -> &mut Vault;
 }
@@ -36,6 +57,10 @@ pub trait HasVault {
 macro_rules! impl_has_vault {
     ($($t: ty), + $(,)?) => ($(
         impl $crate::instructions::reconcile::HasVault for $t {
+            fn vault_key(&self) -> Pubkey {
+                self.vault.key()
+            }
+
             fn vault(&self) -> &Vault {
                 self.vault.deref()
             }
@@ -47,7 +72,108 @@ macro_rules! impl_has_vault {
     )+)
 }
 
-pub fn handle<T: LendingMarket + HasVault>(ctx: Context<T>, withdraw_option: u64) -> Result<()> {
+/// Returns the reserve tokens that an instruction takes out of the vault, if it is a withdraw,
+/// a withdrawal request fulfilment or a collection of fees in reserve tokens from the same vault
+/// Fulfilments only count if the request that they fulfil is among `accounts`
+fn reserve_tokens_taken_out(
+    vault_key: &Pubkey,
+    vault: &Vault,
+    lp_token_supply: u64,
+    accounts: &[AccountInfo],
+    clock: &Clock,
+    ix: &Instruction,
+) -> Option<u64> {
+    (ix.program_id == crate::ID
+        && ix.data.len() >= 8
+        && ix
+            .accounts
+            .first()
+            .map_or(false, |meta| meta.pubkey == *vault_key))
+    .as_option()?;
+
+    let (discriminator, data) = ix.data.split_at(8);
+
+    if discriminator == crate::instruction::Withdraw::discriminator() {
+        let withdraw = crate::instruction::Withdraw::try_from_slice(data).ok()?;
+        // Withdraws price lp tokens without the profit that is still locked
+        crate::math::calc_lp_to_reserve(
+            withdraw.lp_token_amount,
+            lp_token_supply,
+            vault.unlocked_value(clock.slot).ok()?,
+        )
+    } else if discriminator == crate::instruction::FulfilWithdrawal::discriminator() {
+        let request_key = ix.accounts.get(FULFIL_WITHDRAWAL_REQUEST_ACCOUNT)?.pubkey;
+        let request = accounts
+            .iter()
+            .find(|account| account.key == &request_key)
+            .and_then(|account| Account::<WithdrawalRequest>::try_from(account).ok())?;
+
+        // Cancelled requests are closed without taking anything out
+        (request.status == WithdrawalRequestStatus::Queued).as_option()?;
+        match request.price_policy {
+            WithdrawalPricePolicy::AtRequest => Some(request.reserve_token_amount),
+            WithdrawalPricePolicy::AtFulfil => crate::math::calc_lp_to_reserve(
+                request.lp_token_amount,
+                lp_token_supply,
+                vault.unlocked_value(clock.slot).ok()?,
+            ),
+        }
+    } else if discriminator == crate::instruction::CollectFees::discriminator()
+        && vault.config.fee_denomination == FeeDenomination::ReserveToken
+    {
+        vault
+            .calculate_fees(vault.value.value, lp_token_supply, clock.unix_timestamp)
+            .ok()
+            .map(|(total_fees, _)| total_fees)
+    } else {
+        None
+    }
+}
+
+/// Returns an error unless an instruction after the current one takes at least
+/// `reserve_token_amount` out of the same vault, either as a withdraw or as fees
+/// Fulfilling a queued withdrawal request counts as a withdraw
+///
+/// The instructions sysvar, the vault's lp token mint and the withdrawal requests
+/// that following fulfilments fulfil are taken from `remaining_accounts`
+pub fn verify_withdraw_follows(
+    vault_key: &Pubkey,
+    vault: &Vault,
+    remaining_accounts: &[AccountInfo],
+    reserve_token_amount: u64,
+) -> Result<()> {
+    let instructions_sysvar = remaining_accounts
+        .iter()
+        .find(|account| account.key == &sysvar::instructions::ID)
+        .ok_or(ErrorCode::InsufficientAccounts)?;
+    let lp_token_mint = remaining_accounts
+        .iter()
+        .find(|account| account.key == &vault.lp_token_mint)
+        .map(Account::<Mint>::try_from)
+        .ok_or(ErrorCode::InsufficientAccounts)??;
+    let current_index = load_current_index_checked(instructions_sysvar)? as usize;
+    let clock = Clock::get()?;
+
+    (current_index + 1..)
+        .map_while(|index| load_instruction_at_checked(index, instructions_sysvar).ok())
+        .filter_map(|ix| {
+            reserve_tokens_taken_out(
+                vault_key,
+                vault,
+                lp_token_mint.supply,
+                remaining_accounts,
+                &clock,
+                &ix,
+            )
+        })
+        .any(|amount| amount >= reserve_token_amount)
+        .ok_or_else(|| ErrorCode::WithdrawInstructionNotFound.into())
+}
+
+pub fn handle<'info, T: LendingMarket + HasVault>(
+    ctx: Context<'_, '_, '_, 'info, T>,
+    withdraw_option: u64,
+) -> Result<()> {
     // Check that reconciles are not halted
     (!ctx
         .accounts
@@ -56,7 +182,7 @@ pub fn handle<T: LendingMarket + HasVault>(ctx: Context<T>, withdraw_option: u64
         .contains(VaultFlags::HALT_RECONCILES))
     .ok_or::<Error>(ErrorCode::HaltedVault.into())?;
 
-    let provider = ctx.accounts.provider();
+    let index = ctx.accounts.yield_source_index()?;
     match withdraw_option {
         // Normal case where reconcile is being called after rebalance
         0 => {
@@ -64,7 +190,7 @@ pub fn handle<T: LendingMarket + HasVault>(ctx: Context<T>, withdraw_option: u64
             let current_value = ctx
                 .accounts
                 .convert_amount_lp_to_reserve(lp_tokens_in_vault)?;
-            let allocation = ctx.accounts.vault().target_allocations[provider];
+            let allocation = ctx.accounts.vault().yield_sources[index].target_allocation;
             #[cfg(feature = "debug")]
             {
                 msg!("Desired allocation: {}", allocation.val);
@@ -80,8 +206,14 @@ pub fn handle<T: LendingMarket + HasVault>(ctx: Context<T>, withdraw_option: u64
             match allocation.value.checked_sub(current_value) {
                 Some(tokens_to_deposit) => {
                     // Make sure that the amount deposited is not more than the vault has in reserves
-                    let tokens_to_deposit_checked =
-                        cmp::min(tokens_to_deposit, ctx.accounts.reserve_tokens_in_vault());
+                    // on top of what it keeps idle
+                    let idle_reserve_floor = ctx.accounts.vault().idle_reserve_floor()?;
+                    let tokens_to_deposit_checked = cmp::min(
+                        tokens_to_deposit,
+                        ctx.accounts
+                            .reserve_tokens_in_vault()
+                            .saturating_sub(idle_reserve_floor),
+                    );
 
                     #[cfg(feature = "debug")]
                     msg!("Depositing {}", tokens_to_deposit_checked);
@@ -104,12 +236,40 @@ pub fn handle<T: LendingMarket + HasVault>(ctx: Context<T>, withdraw_option: u64
                 }
             }
 
-            ctx.accounts.vault_mut().target_allocations[provider].reset();
+            ctx.accounts.vault_mut().yield_sources[index].target_allocation.reset();
         }
-        // Extra case where reconcile is being called in same tx as withdraw of by vault owner to emergency brake
+        // Extra case where reconcile is being called in same tx as a withdraw or a fee collection
+        // in reserve tokens that the vault cannot cover, or by the vault owner to emergency brake
         _ => {
-            // TODO check that tx is signed by owner OR there is a withdraw tx later with withdraw_option <= withdraw_amount
-            let tokens_to_redeem = ctx.accounts.convert_amount_reserve_to_lp(withdraw_option)?;
+            let vault = ctx.accounts.vault();
+            let signed_by_owner = ctx
+                .remaining_accounts
+                .iter()
+                .any(|account| account.is_signer && account.key == &vault.owner);
+
+            let tokens_to_redeem = if signed_by_owner {
+                ctx.accounts.convert_amount_reserve_to_lp(withdraw_option)?
+            } else {
+                verify_withdraw_follows(
+                    &ctx.accounts.vault_key(),
+                    vault,
+                    ctx.remaining_accounts,
+                    withdraw_option,
+                )?;
+
+                // Only redeem what is missing from the vault reserves to cover the withdraw
+                let reserve_shortfall =
+                    withdraw_option.saturating_sub(ctx.accounts.reserve_tokens_in_vault());
+                if reserve_shortfall == 0 {
+                    return Ok(());
+                }
+
+                // Round up so that the lending market's exchange rate rounding cannot leave the withdraw short
+                ctx.accounts
+                    .convert_amount_reserve_to_lp(reserve_shortfall)?
+                    .checked_add(1)
+                    .ok_or(ErrorCode::OverflowError)?
+            };
 
             // Make sure that the amount to redeem is not more than the vault has
             let tokens_to_redeem_checked =
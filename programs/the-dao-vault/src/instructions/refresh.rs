@@ -5,8 +5,12 @@ use anchor_lang::prelude::*;
 use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount};
 use port_anchor_adaptor::{port_lending_id, PortReserve};
 
+use boolinator::Boolinator;
+
 use crate::adapters::{solend, SolendReserve};
 use crate::errors::ErrorCode;
+use crate::reconcile::HasVault;
+use crate::state::VaultFlags;
 
 pub trait Refresher<'info> {
     fn update_actual_allocation(&mut self, remaining_accounts: &[AccountInfo<'info>])
@@ -14,10 +18,20 @@ pub trait Refresher<'info> {
 }
 
 /// Refreshes the reserves of downstream lending markets
-pub fn handler<'info, T: Refresher<'info>>(ctx: Context<'_, '_, '_, 'info, T>) -> Result<()> {
+pub fn handler<'info, T: Refresher<'info> + HasVault>(
+    ctx: Context<'_, '_, '_, 'info, T>,
+) -> Result<()> {
     #[cfg(feature = "debug")]
     msg!("Refreshing lending pool");
 
+    // Check that refreshes are not halted
+    (!ctx
+        .accounts
+        .vault()
+        .flags()
+        .contains(VaultFlags::HALT_REFRESHED))
+    .ok_or(ErrorCode::HaltedVault)?;
+
     ctx.accounts
         .update_actual_allocation(ctx.remaining_accounts)
 }
@@ -0,0 +1,226 @@
+use boolinator::Boolinator;
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Burn, Mint, Token, TokenAccount, Transfer};
+
+use crate::{
+    errors::ErrorCode,
+    math::calc_lp_to_reserve,
+    state::{
+        UserPosition, Vault, VaultFlags, WithdrawalPricePolicy, WithdrawalRequest,
+        WithdrawalRequestStatus,
+    },
+};
+
+#[event]
+pub struct WithdrawalRequestedEvent {
+    vault: Pubkey,
+    user: Pubkey,
+    index: u64,
+    lp_token_amount: u64,
+    /// Zero when the request is priced at fulfilment
+    reserve_token_amount: u64,
+}
+
+#[derive(Accounts)]
+pub struct RequestWithdraw<'info> {
+    /// Vault state account
+    /// Checks that refresh has been called in the same slot
+    /// Checks that the accounts passed in are correct
+    #[account(
+        mut,
+        constraint = !vault.value.last_update.is_stale(clock.slot)? @ ErrorCode::VaultIsNotRefreshed,
+        has_one = lp_token_mint,
+        has_one = vault_authority,
+    )]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Authority that the vault uses for lp token mints/burns and transfers to/from downstream assets
+    pub vault_authority: AccountInfo<'info>,
+
+    /// Mint for the vault's lp token
+    #[account(mut)]
+    pub lp_token_mint: Box<Account<'info, Mint>>,
+
+    /// Token account that escrows the lp tokens of withdrawal requests priced at fulfilment
+    #[account(mut, seeds = [vault.key().as_ref(), b"withdrawal_queue".as_ref()], bump)]
+    pub withdrawal_queue_lp_token: Box<Account<'info, TokenAccount>>,
+
+    /// Request at the tail of the withdrawal queue
+    #[account(
+        init,
+        payer = user_authority,
+        space = 8 + WithdrawalRequest::LEN,
+        seeds = [
+            vault.key().as_ref(),
+            b"withdrawal_request".as_ref(),
+            vault.withdrawal_queue_tail.to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub withdrawal_request: Box<Account<'info, WithdrawalRequest>>,
+
+    /// Token account from which vault lp tokens are burned or escrowed
+    #[account(mut)]
+    pub user_lp_token: Box<Account<'info, TokenAccount>>,
+
+    /// Authority of the user_lp_token account
+    /// Must be signer, pays for the request account
+    #[account(mut)]
+    pub user_authority: Signer<'info>,
+
+    /// Position of the user authority in the vault, updated once it has been initialized
+    #[account(
+        mut,
+        seeds = [vault.key().as_ref(), b"position".as_ref(), user_authority.key().as_ref()],
+        bump,
+    )]
+    pub user_position: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+
+    pub clock: Sysvar<'info, Clock>,
+    // Remaining accounts must hold the referrer account of the user position's referrer
+    // while it has referred value
+}
+
+impl<'info> RequestWithdraw<'info> {
+    /// CpiContext for burning vault lp tokens from user account
+    fn burn_context(&self) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Burn {
+                mint: self.lp_token_mint.to_account_info(),
+                to: self.user_lp_token.to_account_info(),
+                authority: self.user_authority.to_account_info(),
+            },
+        )
+    }
+
+    /// CpiContext for escrowing vault lp tokens from user account
+    fn transfer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Transfer {
+                from: self.user_lp_token.to_account_info(),
+                to: self.withdrawal_queue_lp_token.to_account_info(),
+                authority: self.user_authority.to_account_info(),
+            },
+        )
+    }
+}
+
+/// Queues a withdraw for when the vault holds enough reserve tokens to fulfil it
+///
+/// Depending on the withdrawal price policy, the lp tokens are either burned now for the
+/// reserve tokens they are worth in this slot, or escrowed and priced at fulfilment
+/// The withdrawal fee is locked in now and charged at fulfilment
+/// Fulfilment pays the user's associated reserve token account
+pub fn handler(ctx: Context<RequestWithdraw>, lp_token_amount: u64) -> Result<()> {
+    #[cfg(feature = "debug")]
+    msg!("Requesting a withdraw of {} lp tokens", lp_token_amount);
+
+    // Check that withdraws are not halted
+    (!ctx
+        .accounts
+        .vault
+        .flags()
+        .contains(VaultFlags::HALT_DEPOSITS_WITHDRAWS))
+    .ok_or(ErrorCode::HaltedVault)?;
+
+    (lp_token_amount > 0).ok_or(ErrorCode::EmptyWithdrawalRequest)?;
+
+    let clock = &ctx.accounts.clock;
+    let vault = &ctx.accounts.vault;
+    let price_policy = vault.config.withdrawal_price_policy;
+
+    let mut user_position = UserPosition::if_initialized(&ctx.accounts.user_position)?;
+    let (decayed_lp_tokens, decayed_fee_bps) =
+        vault.decayed_withdrawal_fee(user_position.as_deref(), clock.slot)?;
+
+    let reserve_token_amount = match price_policy {
+        WithdrawalPricePolicy::AtRequest => {
+            let reserve_token_amount = calc_lp_to_reserve(
+                lp_token_amount,
+                ctx.accounts.lp_token_mint.supply,
+                vault.unlocked_value(clock.slot)?,
+            )
+            .ok_or(ErrorCode::MathError)?;
+
+            token::burn(ctx.accounts.burn_context(), lp_token_amount)?;
+
+            // The request is owed a fixed amount, so it stops sharing in gains and losses
+            let vault = &mut ctx.accounts.vault;
+            vault.value.value = vault
+                .value
+                .value
+                .checked_sub(reserve_token_amount)
+                .ok_or(ErrorCode::MathError)?;
+            vault.queued_withdrawal_value = vault
+                .queued_withdrawal_value
+                .checked_add(reserve_token_amount)
+                .ok_or(ErrorCode::OverflowError)?;
+
+            reserve_token_amount
+        }
+        WithdrawalPricePolicy::AtFulfil => {
+            token::transfer(ctx.accounts.transfer_context(), lp_token_amount)?;
+
+            let vault = &mut ctx.accounts.vault;
+            vault.queued_lp_tokens = vault
+                .queued_lp_tokens
+                .checked_add(lp_token_amount)
+                .ok_or(ErrorCode::OverflowError)?;
+
+            0
+        }
+    };
+
+    // Kept on the request so that a cancel can put the position back as it was
+    let (position_lp_tokens, released_referred_value) = match user_position.as_mut() {
+        Some(user_position) => {
+            let position_lp_tokens = lp_token_amount.min(user_position.lp_token_amount);
+            let vault_key = ctx.accounts.vault.key();
+            let released_referred_value = ctx.accounts.vault.release_referred_value(
+                &vault_key,
+                user_position,
+                ctx.remaining_accounts,
+                lp_token_amount,
+            )?;
+            user_position.record_withdraw(0, lp_token_amount)?;
+            user_position.exit(&crate::ID)?;
+            (position_lp_tokens, released_referred_value)
+        }
+        None => (0, 0),
+    };
+
+    let vault = &mut ctx.accounts.vault;
+    let index = vault.withdrawal_queue_tail;
+    vault.withdrawal_queue_tail = index.checked_add(1).ok_or(ErrorCode::OverflowError)?;
+
+    let withdrawal_request = &mut ctx.accounts.withdrawal_request;
+    withdrawal_request.vault = vault.key();
+    withdrawal_request.owner = ctx.accounts.user_authority.key();
+    withdrawal_request.index = index;
+    withdrawal_request.status = WithdrawalRequestStatus::Queued;
+    withdrawal_request.price_policy = price_policy;
+    withdrawal_request.lp_token_amount = lp_token_amount;
+    withdrawal_request.reserve_token_amount = reserve_token_amount;
+    withdrawal_request.withdrawal_fee_bps = vault.config.withdrawal_fee_bps as u64;
+    withdrawal_request.decayed_withdrawal_fee_bps = decayed_fee_bps;
+    withdrawal_request.decayed_lp_tokens = decayed_lp_tokens;
+    withdrawal_request.request_slot = clock.slot;
+    withdrawal_request.position_lp_tokens = position_lp_tokens;
+    withdrawal_request.released_referred_value = released_referred_value;
+
+    emit!(WithdrawalRequestedEvent {
+        vault: vault.key(),
+        user: ctx.accounts.user_authority.key(),
+        index,
+        lp_token_amount,
+        reserve_token_amount,
+    });
+    Ok(())
+}
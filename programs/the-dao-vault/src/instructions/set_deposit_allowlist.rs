@@ -0,0 +1,45 @@
+use anchor_lang::prelude::*;
+
+use crate::state::Vault;
+
+#[event]
+pub struct DepositAllowlistChangedEvent {
+    vault: Pubkey,
+    merkle_root: [u8; 32],
+    enabled: bool,
+}
+
+#[derive(Accounts)]
+pub struct SetDepositAllowlist<'info> {
+    /// Vault state account
+    #[account(mut, has_one = owner)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Owner of the vault
+    /// Only this account can change the deposit allowlist
+    pub owner: Signer<'info>,
+}
+
+/// Sets the Merkle root of the (user, max deposit) pairs that can deposit
+///
+/// While enabled, deposits have to prove their pair and pass in the user position,
+/// whose total deposited is checked against the max deposit
+/// Disabling the allowlist returns the vault to open deposits
+pub fn handler(
+    ctx: Context<SetDepositAllowlist>,
+    merkle_root: [u8; 32],
+    enabled: bool,
+) -> Result<()> {
+    #[cfg(feature = "debug")]
+    msg!("Setting deposit allowlist enabled to {}", enabled);
+
+    ctx.accounts.vault.deposit_allowlist_root = merkle_root;
+    ctx.accounts.vault.deposit_allowlist_enabled = enabled;
+
+    emit!(DepositAllowlistChangedEvent {
+        vault: ctx.accounts.vault.key(),
+        merkle_root,
+        enabled,
+    });
+    Ok(())
+}
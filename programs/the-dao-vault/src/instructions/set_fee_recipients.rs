@@ -0,0 +1,89 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+
+use boolinator::Boolinator;
+
+use crate::{
+    errors::ErrorCode,
+    state::{FeeRecipient, Vault},
+};
+
+#[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug)]
+pub struct FeeRecipientArg {
+    pub token_account: Pubkey,
+    pub share_bps: u16,
+}
+
+#[event]
+pub struct FeeRecipientsChangedEvent {
+    vault: Pubkey,
+    old_fee_recipients: Vec<FeeRecipient>,
+    new_fee_recipients: Vec<FeeRecipient>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeRecipients<'info> {
+    /// Vault state account
+    /// Checks that fees have been collected in the same slot
+    /// so that fees accrued so far are split with the old shares
+    #[account(mut, has_one = owner, has_one = lp_token_mint, constraint = vault.last_fee_collection.slots_elapsed(clock.slot)? == 0 @ ErrorCode::FeesNotCollected)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Mint for the vault's lp token
+    pub lp_token_mint: AccountInfo<'info>,
+
+    /// Owner of the vault
+    /// Only this account can change the fee recipients
+    pub owner: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    // Remaining accounts are the token accounts of the new fee recipients in order
+}
+
+/// Replaces the accounts that the non-referral fees are split between
+///
+/// Fails unless every recipient is a vault lp token account and the shares add up to 100%
+/// Fees paid in reserve tokens go to the associated token accounts of the lp token account owners
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, SetFeeRecipients<'info>>,
+    fee_recipients: Vec<FeeRecipientArg>,
+) -> Result<()> {
+    (ctx.remaining_accounts.len() == fee_recipients.len())
+        .ok_or(ErrorCode::InsufficientAccounts)?;
+
+    let new_fee_recipients = fee_recipients
+        .iter()
+        .zip(ctx.remaining_accounts)
+        .map(|(recipient, account)| {
+            let token_account = Account::<TokenAccount>::try_from(account)?;
+            (token_account.key() == recipient.token_account
+                && token_account.mint == ctx.accounts.lp_token_mint.key())
+            .ok_or(ErrorCode::InvalidAccount)?;
+
+            Ok(FeeRecipient::new(
+                recipient.token_account,
+                token_account.owner,
+                recipient.share_bps,
+            ))
+        })
+        .collect::<Result<Vec<FeeRecipient>>>()?;
+
+    let old_fee_recipients = ctx
+        .accounts
+        .vault
+        .registered_fee_recipients()
+        .copied()
+        .collect();
+
+    #[cfg(feature = "debug")]
+    msg!("Setting fee recipients to {:?}", new_fee_recipients);
+
+    ctx.accounts.vault.set_fee_recipients(&new_fee_recipients)?;
+
+    emit!(FeeRecipientsChangedEvent {
+        vault: ctx.accounts.vault.key(),
+        old_fee_recipients,
+        new_fee_recipients,
+    });
+    Ok(())
+}
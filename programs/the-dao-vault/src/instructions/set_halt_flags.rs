@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+
+use crate::state::Vault;
+
+#[event]
+pub struct HaltFlagsChangedEvent {
+    vault: Pubkey,
+    old_flags: u32,
+    new_flags: u32,
+}
+
+#[derive(Accounts)]
+pub struct SetHaltFlags<'info> {
+    /// Vault state account
+    #[account(mut, has_one = owner)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Owner of the vault
+    /// Only this account can halt or resume the vault
+    pub owner: Signer<'info>,
+}
+
+/// Sets the halt flags of the vault
+///
+/// Fails if the bits passed in do not map to valid VaultFlags
+pub fn handler(ctx: Context<SetHaltFlags>, flags: u32) -> Result<()> {
+    let old_flags = ctx.accounts.vault.flags().bits();
+
+    #[cfg(feature = "debug")]
+    msg!("Setting halt flags from {:#b} to {:#b}", old_flags, flags);
+
+    ctx.accounts.vault.set_flags(flags)?;
+
+    emit!(HaltFlagsChangedEvent {
+        vault: ctx.accounts.vault.key(),
+        old_flags,
+        new_flags: flags,
+    });
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use anchor_lang::prelude::*;
+
+use boolinator::Boolinator;
+
+use crate::{
+    errors::ErrorCode,
+    state::{Vault, MAX_YIELD_SOURCES},
+};
+
+#[event]
+pub struct YieldSourceEnabledChangedEvent {
+    vault: Pubkey,
+    index: u8,
+    enabled: bool,
+}
+
+#[derive(Accounts)]
+pub struct SetYieldSourceEnabled<'info> {
+    /// Vault state account
+    #[account(mut, has_one = owner)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Owner of the vault
+    /// Only this account can enable or disable yield sources
+    pub owner: Signer<'info>,
+}
+
+/// Enables or disables a registered yield source
+///
+/// Disabled yield sources are skipped by rebalance and get a zero target allocation,
+/// so that the next reconcile moves their funds back into the vault
+pub fn handler(ctx: Context<SetYieldSourceEnabled>, index: u8, enabled: bool) -> Result<()> {
+    #[cfg(feature = "debug")]
+    msg!("Setting yield source {} enabled to {}", index, enabled);
+
+    let index = index as usize;
+    (index < MAX_YIELD_SOURCES && ctx.accounts.vault.yield_sources[index].is_registered())
+        .ok_or(ErrorCode::InvalidAccount)?;
+
+    ctx.accounts.vault.yield_sources[index].enabled = enabled;
+
+    emit!(YieldSourceEnabledChangedEvent {
+        vault: ctx.accounts.vault.key(),
+        index: index as u8,
+        enabled,
+    });
+    Ok(())
+}
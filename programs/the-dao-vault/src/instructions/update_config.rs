@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    errors::ErrorCode,
+    instructions::VaultConfigArg,
+    state::{Vault, VaultConfig},
+};
+
+#[event]
+pub struct ConfigChangedEvent {
+    vault: Pubkey,
+    old_config: VaultConfig,
+    new_config: VaultConfig,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    /// Vault state account
+    /// Checks that fees have been collected in the same slot
+    /// so that fees are crystallized at the old config before it changes
+    #[account(mut, has_one = owner, constraint = vault.last_fee_collection.slots_elapsed(clock.slot)? == 0 @ ErrorCode::FeesNotCollected)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Owner of the vault
+    /// Only this account can update the config
+    pub owner: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+/// Updates the vault config
+///
+/// The new config goes through the same validation as at vault initialization
+/// A new profit unlock period applies to the profit still locked from this slot on
+pub fn handler(ctx: Context<UpdateConfig>, config: VaultConfigArg) -> Result<()> {
+    let old_config = ctx.accounts.vault.config;
+    let new_config = VaultConfig::new(config)?;
+
+    #[cfg(feature = "debug")]
+    msg!("Updating config from {:?} to {:?}", old_config, new_config);
+
+    // Profit that is already locked keeps unlocking at the old rate up to now
+    ctx.accounts
+        .vault
+        .settle_locked_profit(ctx.accounts.clock.slot)?;
+    ctx.accounts.vault.config = new_config;
+
+    emit!(ConfigChangedEvent {
+        vault: ctx.accounts.vault.key(),
+        old_config,
+        new_config,
+    });
+    Ok(())
+}
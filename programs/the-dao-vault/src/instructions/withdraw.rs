@@ -0,0 +1,244 @@
+use std::convert::Into;
+
+use boolinator::Boolinator;
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Burn, Mint, Token, TokenAccount, Transfer};
+
+use crate::{
+    errors::ErrorCode,
+    math::{calc_lp_to_reserve, calc_withdrawal_fee},
+    state::{UserPosition, Vault, VaultFlags, WithdrawalFeeDestination},
+};
+
+#[event]
+pub struct WithdrawEvent {
+    vault: Pubkey,
+    user: Pubkey,
+    amount: u64,
+    withdrawal_fee: u64,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    /// Vault state account
+    /// Checks that refresh has been called in the same slot
+    /// Checks that the accounts passed in are correct
+    #[account(mut, constraint = !vault.value.last_update.is_stale(clock.slot)? @ ErrorCode::VaultIsNotRefreshed, has_one = lp_token_mint, has_one = vault_authority, has_one = vault_reserve_token)]
+    pub vault: Box<Account<'info, Vault>>,
+
+    /// Authority that the vault uses for lp token mints/burns and transfers to/from downstream assets
+    pub vault_authority: AccountInfo<'info>,
+
+    /// Token account for the vault's reserve tokens
+    #[account(mut)]
+    pub vault_reserve_token: Box<Account<'info, TokenAccount>>,
+
+    /// Mint for the vault's lp token
+    #[account(mut)]
+    pub lp_token_mint: Box<Account<'info, Mint>>,
+
+    /// Token account from which vault lp tokens are burned
+    #[account(mut)]
+    pub user_lp_token: Box<Account<'info, TokenAccount>>,
+
+    /// Account where reserve tokens are transfered to
+    #[account(mut)]
+    pub user_reserve_token: Box<Account<'info, TokenAccount>>,
+
+    /// Authority of the user_lp_token account
+    /// Must be signer
+    pub user_authority: Signer<'info>,
+
+    /// Position of the user authority in the vault, updated once it has been initialized
+    #[account(
+        mut,
+        seeds = [vault.key().as_ref(), b"position".as_ref(), user_authority.key().as_ref()],
+        bump,
+    )]
+    pub user_position: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub clock: Sysvar<'info, Clock>,
+    // Remaining accounts must hold the referrer account of the user position's referrer
+    // while it has referred value, and, when withdrawal fees go to the fee receiver,
+    // the associated reserve token account of the first fee recipient
+}
+
+impl<'info> Withdraw<'info> {
+    /// CpiContext for burning vault lp tokens from user account
+    fn burn_context(&self) -> CpiContext<'_, '_, '_, 'info, Burn<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Burn {
+                mint: self.lp_token_mint.to_account_info(),
+                to: self.user_lp_token.to_account_info(),
+                authority: self.user_authority.to_account_info(),
+            },
+        )
+    }
+
+    /// CpiContext for transfering reserve tokens from vault to user
+    fn transfer_context(&self) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        self.transfer_to_context(self.user_reserve_token.to_account_info())
+    }
+
+    /// CpiContext for transfering reserve tokens from vault to any token account
+    fn transfer_to_context(
+        &self,
+        to: AccountInfo<'info>,
+    ) -> CpiContext<'_, '_, '_, 'info, Transfer<'info>> {
+        CpiContext::new(
+            self.token_program.to_account_info(),
+            Transfer {
+                from: self.vault_reserve_token.to_account_info(),
+                to,
+                authority: self.vault_authority.clone(),
+            },
+        )
+    }
+
+    /// Returns the withdrawal fee on `reserve_tokens` withdrawn for `lp_token_amount`
+    /// Lp tokens tracked by the user position get the fee decayed since the last deposit,
+    /// the rest are charged the full fee
+    fn withdrawal_fee(
+        &self,
+        reserve_tokens: u64,
+        lp_token_amount: u64,
+        user_position: Option<&UserPosition>,
+    ) -> Result<u64> {
+        let (decayed_lp_tokens, decayed_fee_bps) = self
+            .vault
+            .decayed_withdrawal_fee(user_position, self.clock.slot)?;
+
+        calc_withdrawal_fee(
+            reserve_tokens,
+            lp_token_amount,
+            decayed_lp_tokens,
+            self.vault.config.withdrawal_fee_bps as u64,
+            decayed_fee_bps,
+        )
+        .ok_or_else(|| ErrorCode::MathError.into())
+    }
+}
+
+/// Withdraw from the vault
+///
+/// Burns the user's lp tokens and transfers their share of reserve tokens
+/// Fails if fewer than `min_reserve_out` reserve tokens would be transferred
+/// or if the slot is past `max_slot`
+/// The withdrawal fee is taken out of the user's share before the transfer
+/// Records the withdraw in the user position once it has been initialized
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, Withdraw<'info>>,
+    lp_token_amount: u64,
+    min_reserve_out: u64,
+    max_slot: u64,
+) -> Result<()> {
+    #[cfg(feature = "debug")]
+    msg!("Withdrawing {} lp tokens", lp_token_amount);
+
+    // Check that withdraws are not halted
+    (!ctx
+        .accounts
+        .vault
+        .flags()
+        .contains(VaultFlags::HALT_DEPOSITS_WITHDRAWS))
+    .ok_or(ErrorCode::HaltedVault)?;
+
+    (ctx.accounts.clock.slot <= max_slot).ok_or(ErrorCode::TransactionExpired)?;
+
+    let vault = &ctx.accounts.vault;
+
+    let mut user_position = UserPosition::if_initialized(&ctx.accounts.user_position)?;
+
+    let reserve_tokens_withdrawn = calc_lp_to_reserve(
+        lp_token_amount,
+        ctx.accounts.lp_token_mint.supply,
+        vault.unlocked_value(ctx.accounts.clock.slot)?,
+    )
+    .ok_or(ErrorCode::MathError)?;
+
+    let withdrawal_fee = ctx.accounts.withdrawal_fee(
+        reserve_tokens_withdrawn,
+        lp_token_amount,
+        user_position.as_deref(),
+    )?;
+    let reserve_tokens_to_transfer = reserve_tokens_withdrawn
+        .checked_sub(withdrawal_fee)
+        .ok_or(ErrorCode::MathError)?;
+
+    (reserve_tokens_to_transfer >= min_reserve_out)
+        .ok_or(ErrorCode::InsufficientReserveTokensOut)?;
+
+    #[cfg(feature = "debug")]
+    msg!("Transferring {} reserve tokens", reserve_tokens_to_transfer);
+
+    token::transfer(
+        ctx.accounts
+            .transfer_context()
+            .with_signer(&[&vault.authority_seeds()]),
+        reserve_tokens_to_transfer,
+    )?;
+
+    // Fees left in the vault raise the share price of the remaining lp token holders
+    let reserve_tokens_out = match vault.config.withdrawal_fee_destination {
+        WithdrawalFeeDestination::Vault => reserve_tokens_to_transfer,
+        WithdrawalFeeDestination::FeeReceiver => {
+            if withdrawal_fee > 0 {
+                let fee_receiver_address = vault.withdrawal_fee_receiver();
+                let fee_receiver = ctx
+                    .remaining_accounts
+                    .iter()
+                    .find(|account| account.key() == fee_receiver_address)
+                    .ok_or(ErrorCode::InsufficientAccounts)?;
+
+                #[cfg(feature = "debug")]
+                msg!(
+                    "Transferring {} reserve tokens in withdrawal fees",
+                    withdrawal_fee
+                );
+
+                token::transfer(
+                    ctx.accounts
+                        .transfer_to_context(fee_receiver.clone())
+                        .with_signer(&[&vault.authority_seeds()]),
+                    withdrawal_fee,
+                )?;
+            }
+            reserve_tokens_withdrawn
+        }
+    };
+
+    token::burn(ctx.accounts.burn_context(), lp_token_amount)?;
+
+    // This is so that the SDK can read an up-to-date total value without calling refresh
+    ctx.accounts.vault.value.value = ctx
+        .accounts
+        .vault
+        .value
+        .value
+        .checked_sub(reserve_tokens_out)
+        .ok_or(ErrorCode::MathError)?;
+
+    if let Some(user_position) = user_position.as_mut() {
+        let vault_key = ctx.accounts.vault.key();
+        ctx.accounts.vault.release_referred_value(
+            &vault_key,
+            user_position,
+            ctx.remaining_accounts,
+            lp_token_amount,
+        )?;
+        user_position.record_withdraw(reserve_tokens_to_transfer, lp_token_amount)?;
+        user_position.exit(&crate::ID)?;
+    }
+
+    emit!(WithdrawEvent {
+        vault: ctx.accounts.vault.key(),
+        user: ctx.accounts.user_authority.key(),
+        amount: reserve_tokens_to_transfer,
+        withdrawal_fee,
+    });
+    Ok(())
+}
@@ -5,6 +5,7 @@ pub mod asset_container;
 pub mod errors;
 pub mod instructions;
 pub mod math;
+pub mod merkle;
 pub mod reserves;
 pub mod state;
 
@@ -17,10 +18,230 @@ declare_id!("CYrQ5HhWsznvqX4dNMY8RuBxJhZQNjV6kZYynG22dqjQ");
 pub mod the_dao_vault {
     use super::*;
 
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
-        Ok(())
+    pub fn initialize(
+        ctx: Context<Initialize>,
+        bumps: InitBumpSeeds,
+        config: VaultConfigArg,
+    ) -> Result<()> {
+        instructions::init_vault::handler(ctx, bumps, config)
+    }
+
+    pub fn update_config(ctx: Context<UpdateConfig>, config: VaultConfigArg) -> Result<()> {
+        instructions::update_config::handler(ctx, config)
+    }
+
+    pub fn set_halt_flags(ctx: Context<SetHaltFlags>, flags: u32) -> Result<()> {
+        instructions::set_halt_flags::handler(ctx, flags)
+    }
+
+    pub fn set_fee_recipients<'info>(
+        ctx: Context<'_, '_, '_, 'info, SetFeeRecipients<'info>>,
+        fee_recipients: Vec<FeeRecipientArg>,
+    ) -> Result<()> {
+        instructions::set_fee_recipients::handler(ctx, fee_recipients)
+    }
+
+    pub fn set_yield_source_enabled(
+        ctx: Context<SetYieldSourceEnabled>,
+        index: u8,
+        enabled: bool,
+    ) -> Result<()> {
+        instructions::set_yield_source_enabled::handler(ctx, index, enabled)
+    }
+
+    pub fn init_user_position(ctx: Context<InitUserPosition>) -> Result<()> {
+        instructions::init_user_position::handler(ctx)
+    }
+
+    pub fn init_referrer(ctx: Context<InitReferrer>) -> Result<()> {
+        instructions::init_referrer::handler(ctx)
+    }
+
+    pub fn claim_referral_fees(ctx: Context<ClaimReferralFees>) -> Result<()> {
+        instructions::claim_referral_fees::handler(ctx)
+    }
+
+    pub fn set_deposit_allowlist(
+        ctx: Context<SetDepositAllowlist>,
+        merkle_root: [u8; 32],
+        enabled: bool,
+    ) -> Result<()> {
+        instructions::set_deposit_allowlist::handler(ctx, merkle_root, enabled)
+    }
+
+    pub fn propose_owner(ctx: Context<ProposeOwner>, new_owner: Pubkey) -> Result<()> {
+        instructions::propose_owner::handler(ctx, new_owner)
+    }
+
+    pub fn accept_owner(ctx: Context<AcceptOwner>) -> Result<()> {
+        instructions::accept_owner::handler(ctx)
+    }
+
+    pub fn init_solend<'info>(
+        ctx: Context<'_, '_, '_, 'info, InitializeSolend<'info>>,
+        bump: u8,
+    ) -> Result<()> {
+        instructions::init_yield_source::handler(ctx, bump)
+    }
+
+    pub fn init_port<'info>(
+        ctx: Context<'_, '_, '_, 'info, InitializePort<'info>>,
+        bump: u8,
+    ) -> Result<()> {
+        instructions::init_yield_source::handler(ctx, bump)
+    }
+
+    pub fn init_jet<'info>(
+        ctx: Context<'_, '_, '_, 'info, InitializeJet<'info>>,
+        bump: u8,
+    ) -> Result<()> {
+        instructions::init_yield_source::handler(ctx, bump)
+    }
+
+    pub fn init_token_lending<'info>(
+        ctx: Context<'_, '_, '_, 'info, InitializeTokenLending<'info>>,
+        bump: u8,
+    ) -> Result<()> {
+        instructions::init_yield_source::handler(ctx, bump)
+    }
+
+    pub fn deposit(
+        ctx: Context<Deposit>,
+        reserve_token_amount: u64,
+        min_lp_out: u64,
+        max_slot: u64,
+        referrer: Option<Pubkey>,
+        allowlist_proof: Option<AllowlistProofArg>,
+    ) -> Result<()> {
+        instructions::deposit::handler(
+            ctx,
+            reserve_token_amount,
+            min_lp_out,
+            max_slot,
+            referrer,
+            allowlist_proof,
+        )
+    }
+
+    pub fn withdraw<'info>(
+        ctx: Context<'_, '_, '_, 'info, Withdraw<'info>>,
+        lp_token_amount: u64,
+        min_reserve_out: u64,
+        max_slot: u64,
+    ) -> Result<()> {
+        instructions::withdraw::handler(ctx, lp_token_amount, min_reserve_out, max_slot)
+    }
+
+    pub fn init_withdrawal_queue(ctx: Context<InitWithdrawalQueue>) -> Result<()> {
+        instructions::init_withdrawal_queue::handler(ctx)
+    }
+
+    pub fn request_withdraw(ctx: Context<RequestWithdraw>, lp_token_amount: u64) -> Result<()> {
+        instructions::request_withdraw::handler(ctx, lp_token_amount)
+    }
+
+    pub fn fulfil_withdrawal<'info>(
+        ctx: Context<'_, '_, '_, 'info, FulfilWithdrawal<'info>>,
+    ) -> Result<()> {
+        instructions::fulfil_withdrawal::handler(ctx)
     }
-}
 
-#[derive(Accounts)]
-pub struct Initialize {}
+    pub fn cancel_withdrawal_request(ctx: Context<CancelWithdrawalRequest>) -> Result<()> {
+        instructions::cancel_withdrawal_request::handler(ctx)
+    }
+
+    pub fn claim_withdrawal(ctx: Context<ClaimWithdrawal>) -> Result<()> {
+        instructions::claim_withdrawal::handler(ctx)
+    }
+
+    pub fn preview_deposit(ctx: Context<Preview>, reserve_token_amount: u64) -> Result<()> {
+        instructions::preview::preview_deposit(ctx, reserve_token_amount)
+    }
+
+    pub fn preview_withdraw(ctx: Context<Preview>, lp_token_amount: u64) -> Result<()> {
+        instructions::preview::preview_withdraw(ctx, lp_token_amount)
+    }
+
+    pub fn convert_to_shares(ctx: Context<Preview>, reserve_token_amount: u64) -> Result<()> {
+        instructions::preview::convert_to_shares(ctx, reserve_token_amount)
+    }
+
+    pub fn convert_to_assets(ctx: Context<Preview>, lp_token_amount: u64) -> Result<()> {
+        instructions::preview::convert_to_assets(ctx, lp_token_amount)
+    }
+
+    pub fn max_deposit(
+        ctx: Context<Preview>,
+        allowlist_proof: Option<AllowlistProofArg>,
+    ) -> Result<()> {
+        instructions::preview::max_deposit(ctx, allowlist_proof)
+    }
+
+    pub fn max_withdraw(ctx: Context<PreviewMaxWithdraw>) -> Result<()> {
+        instructions::preview::max_withdraw(ctx)
+    }
+
+    pub fn rebalance(ctx: Context<Rebalance>, proposed_weights: StrategyWeightsArg) -> Result<()> {
+        instructions::rebalance::handler(ctx, proposed_weights)
+    }
+
+    pub fn refresh_solend<'info>(
+        ctx: Context<'_, '_, '_, 'info, RefreshSolend<'info>>,
+    ) -> Result<()> {
+        instructions::refresh::handler(ctx)
+    }
+
+    pub fn refresh_port<'info>(ctx: Context<'_, '_, '_, 'info, RefreshPort<'info>>) -> Result<()> {
+        instructions::refresh::handler(ctx)
+    }
+
+    pub fn refresh_jet<'info>(ctx: Context<'_, '_, '_, 'info, RefreshJet<'info>>) -> Result<()> {
+        instructions::refresh::handler(ctx)
+    }
+
+    pub fn refresh_token_lending<'info>(
+        ctx: Context<'_, '_, '_, 'info, RefreshTokenLending<'info>>,
+    ) -> Result<()> {
+        instructions::refresh::handler(ctx)
+    }
+
+    pub fn consolidate_refresh<'info>(
+        ctx: Context<'_, '_, '_, 'info, ConsolidateRefresh<'info>>,
+    ) -> Result<()> {
+        instructions::consolidate_refresh::handler(ctx)
+    }
+
+    pub fn collect_fees<'info>(
+        ctx: Context<'_, '_, '_, 'info, CollectFees<'info>>,
+    ) -> Result<()> {
+        instructions::collect_fees::handler(ctx)
+    }
+
+    pub fn reconcile_solend<'info>(
+        ctx: Context<'_, '_, '_, 'info, SolendAccounts<'info>>,
+        withdraw_option: u64,
+    ) -> Result<()> {
+        instructions::reconcile::handle(ctx, withdraw_option)
+    }
+
+    pub fn reconcile_port<'info>(
+        ctx: Context<'_, '_, '_, 'info, PortAccounts<'info>>,
+        withdraw_option: u64,
+    ) -> Result<()> {
+        instructions::reconcile::handle(ctx, withdraw_option)
+    }
+
+    pub fn reconcile_jet<'info>(
+        ctx: Context<'_, '_, '_, 'info, JetAccounts<'info>>,
+        withdraw_option: u64,
+    ) -> Result<()> {
+        instructions::reconcile::handle(ctx, withdraw_option)
+    }
+
+    pub fn reconcile_token_lending<'info>(
+        ctx: Context<'_, '_, '_, 'info, TokenLendingAccounts<'info>>,
+        withdraw_option: u64,
+    ) -> Result<()> {
+        instructions::reconcile::handle(ctx, withdraw_option)
+    }
+}
@@ -1,37 +1,44 @@
 use anchor_lang::prelude::*;
 use std::convert::TryFrom;
 
-use anchor_lang::solana_program::clock::{
-    DEFAULT_TICKS_PER_SECOND, DEFAULT_TICKS_PER_SLOT, SECONDS_PER_DAY,
-};
+use anchor_lang::solana_program::clock::SECONDS_PER_DAY;
 use spl_math::precise_number::PreciseNumber;
 
 use crate::errors::ErrorCode;
 
-pub const INITIAL_COLLATERAL_RATIO: u64 = 1;
+/// Decimals that the lp token has on top of the reserve token
+pub const LP_DECIMALS_OFFSET: u8 = 3;
+
+/// Lp tokens that are always counted in the supply without being owned by anyone
+/// Being 10^LP_DECIMALS_OFFSET times VIRTUAL_RESERVE_TOKENS, an attacker has to donate that many
+/// times a later deposit to round it down by a reserve token, and loses most of the donation
+pub const VIRTUAL_LP_TOKENS: u64 = 10u64.pow(LP_DECIMALS_OFFSET as u32);
+
+/// Reserve tokens that are always counted in the vault value without being deposited
+pub const VIRTUAL_RESERVE_TOKENS: u64 = 1;
+
+/// Largest vault value whose lp tokens fit in a u64 at the initial share price,
+/// which is about 1.8e7 whole tokens of a 9 decimal reserve token or 1.8e10 of a 6 decimal one
+pub const MAX_VAULT_VALUE: u64 = u64::MAX / VIRTUAL_LP_TOKENS - VIRTUAL_RESERVE_TOKENS;
 
 pub fn calc_reserve_to_lp(
     reserve_token_amount: u64,
     lp_token_supply: u64,
     reserve_tokens_in_vault: u64,
 ) -> Option<u64> {
-    match reserve_tokens_in_vault {
-        // Assert that lp supply is 0
-        0 => Some(INITIAL_COLLATERAL_RATIO.checked_mul(reserve_token_amount)?),
-        _ => {
-            let reserve_token_amount = PreciseNumber::new(reserve_token_amount as u128)?;
-            let lp_token_supply = PreciseNumber::new(lp_token_supply as u128)?;
-            let reserve_tokens_in_vault = PreciseNumber::new(reserve_tokens_in_vault as u128)?;
+    let reserve_token_amount = PreciseNumber::new(reserve_token_amount as u128)?;
+    let lp_token_supply =
+        PreciseNumber::new(lp_token_supply as u128 + VIRTUAL_LP_TOKENS as u128)?;
+    let reserve_tokens_in_vault =
+        PreciseNumber::new(reserve_tokens_in_vault as u128 + VIRTUAL_RESERVE_TOKENS as u128)?;
 
-            let lp_tokens_to_mint = lp_token_supply
-                .checked_mul(&reserve_token_amount)?
-                .checked_div(&reserve_tokens_in_vault)?
-                .floor()?
-                .to_imprecise()?;
+    let lp_tokens_to_mint = lp_token_supply
+        .checked_mul(&reserve_token_amount)?
+        .checked_div(&reserve_tokens_in_vault)?
+        .floor()?
+        .to_imprecise()?;
 
-            u64::try_from(lp_tokens_to_mint).ok()
-        }
-    }
+    u64::try_from(lp_tokens_to_mint).ok()
 }
 
 pub fn calc_lp_to_reserve(
@@ -40,8 +47,10 @@ pub fn calc_lp_to_reserve(
     reserve_tokens_in_vault: u64,
 ) -> Option<u64> {
     let lp_token_amount = PreciseNumber::new(lp_token_amount as u128)?;
-    let lp_token_supply = PreciseNumber::new(lp_token_supply as u128)?;
-    let reserve_tokens_in_vault = PreciseNumber::new(reserve_tokens_in_vault as u128)?;
+    let lp_token_supply =
+        PreciseNumber::new(lp_token_supply as u128 + VIRTUAL_LP_TOKENS as u128)?;
+    let reserve_tokens_in_vault =
+        PreciseNumber::new(reserve_tokens_in_vault as u128 + VIRTUAL_RESERVE_TOKENS as u128)?;
 
     let reserve_tokens_to_transfer = lp_token_amount
         .checked_mul(&reserve_tokens_in_vault)?
@@ -52,10 +61,56 @@ pub fn calc_lp_to_reserve(
     u64::try_from(reserve_tokens_to_transfer).ok()
 }
 
-/// Number of slots per year
-/// 63072000
-pub const SLOTS_PER_YEAR: u64 =
-    DEFAULT_TICKS_PER_SECOND / DEFAULT_TICKS_PER_SLOT * SECONDS_PER_DAY * 365;
+/// Scale of the lp token share price, which is in reserve tokens per lp token
+pub const SHARE_PRICE_SCALE: u64 = 1_000_000_000;
+
+/// Returns the scaled value of one lp token, counting the virtual tokens so that it is
+/// defined for an empty vault
+pub fn calc_share_price(lp_token_supply: u64, reserve_tokens_in_vault: u64) -> Option<u64> {
+    let share_price = (reserve_tokens_in_vault as u128 + VIRTUAL_RESERVE_TOKENS as u128)
+        .checked_mul(SHARE_PRICE_SCALE as u128)?
+        .checked_div(lp_token_supply as u128 + VIRTUAL_LP_TOKENS as u128)?;
+
+    u64::try_from(share_price).ok()
+}
+
+/// Returns the reserve tokens gained by the lp token holders above the high-water mark
+pub fn calc_profit_above_high_water_mark(
+    share_price: u64,
+    high_water_mark: u64,
+    lp_token_supply: u64,
+) -> Option<u64> {
+    let profit = (share_price.saturating_sub(high_water_mark) as u128)
+        .checked_mul(lp_token_supply as u128)?
+        .checked_div(SHARE_PRICE_SCALE as u128)?;
+
+    u64::try_from(profit).ok()
+}
+
+/// Returns the lp token weighted average share price after `lp_tokens_minted` are added
+/// to `lp_tokens_held` entered at `entry_share_price` by depositing `reserve_tokens_deposited`
+/// Scaled by SHARE_PRICE_SCALE
+pub fn calc_entry_share_price(
+    entry_share_price: u64,
+    lp_tokens_held: u64,
+    reserve_tokens_deposited: u64,
+    lp_tokens_minted: u64,
+) -> Option<u64> {
+    let lp_tokens = (lp_tokens_held as u128).checked_add(lp_tokens_minted as u128)?;
+    if lp_tokens == 0 {
+        return Some(entry_share_price);
+    }
+
+    (entry_share_price as u128)
+        .checked_mul(lp_tokens_held as u128)?
+        .checked_add((reserve_tokens_deposited as u128).checked_mul(SHARE_PRICE_SCALE as u128)?)?
+        .checked_div(lp_tokens)
+        .and_then(|price| u64::try_from(price).ok())
+}
+
+/// Number of seconds per year
+/// 31536000
+pub const SECONDS_PER_YEAR: u64 = SECONDS_PER_DAY * 365;
 
 pub const ONE_AS_BPS: u64 = 10000;
 
@@ -66,12 +121,141 @@ pub fn calc_carry_fees(profit: u64, fee_bps: u64) -> Result<u64> {
         .ok_or_else(|| ErrorCode::OverflowError.into())
 }
 
-pub fn calc_mgmt_fees(aum: u64, fee_bps: u64, slots_elapsed: u64) -> Result<u64> {
-    [fee_bps, slots_elapsed]
+/// Returns the management fees accrued over `seconds_elapsed` and the remainder to carry over
+///
+/// The remainder is the part of the fee numerator that did not add up to a whole reserve token,
+/// passing it back in on the next call keeps small vaults and frequent collections from losing fees
+pub fn calc_mgmt_fees(
+    aum: u64,
+    fee_bps: u64,
+    seconds_elapsed: u64,
+    remainder: u64,
+) -> Result<(u64, u64)> {
+    let denominator = (ONE_AS_BPS as u128) * (SECONDS_PER_YEAR as u128);
+    let numerator = [fee_bps, seconds_elapsed]
         .iter()
-        .try_fold(aum, |acc, r| acc.checked_mul(*r))
-        .map(|n| n / ONE_AS_BPS / SLOTS_PER_YEAR)
-        .ok_or_else(|| ErrorCode::OverflowError.into())
+        .try_fold(aum as u128, |acc, r| acc.checked_mul(*r as u128))
+        .and_then(|n| n.checked_add(remainder as u128))
+        .ok_or(ErrorCode::OverflowError)?;
+
+    let fees = u64::try_from(numerator / denominator).map_err(|_| ErrorCode::OverflowError)?;
+    // Always smaller than the denominator, which fits in a u64
+    let remainder = (numerator % denominator) as u64;
+
+    Ok((fees, remainder))
+}
+
+/// Returns `amount` decayed linearly to zero over `decay_slots` after `slots_elapsed`
+/// Zero `decay_slots` disables the decay
+pub fn calc_linear_decay(amount: u64, decay_slots: u64, slots_elapsed: u64) -> u64 {
+    match decay_slots {
+        0 => amount,
+        // Never more than amount, so it fits back in a u64
+        _ => (amount as u128 * decay_slots.saturating_sub(slots_elapsed) as u128
+            / decay_slots as u128) as u64,
+    }
+}
+
+/// Returns the part of `locked_profit` that is still locked `slots_elapsed` after it was last updated
+/// Zero `profit_unlock_slots` releases profit right away
+pub fn calc_locked_profit(locked_profit: u64, profit_unlock_slots: u64, slots_elapsed: u64) -> u64 {
+    match profit_unlock_slots {
+        0 => 0,
+        _ => calc_linear_decay(locked_profit, profit_unlock_slots, slots_elapsed),
+    }
+}
+
+/// Returns the locked profit after the vault value moves from `old_value` to `new_value`
+/// Gains are locked on top of `locked_profit`, losses are taken out of it first
+pub fn calc_locked_profit_after_update(
+    locked_profit: u64,
+    old_value: u64,
+    new_value: u64,
+) -> Option<u64> {
+    match new_value.checked_sub(old_value) {
+        Some(gain) => locked_profit.checked_add(gain),
+        None => Some(locked_profit.saturating_sub(old_value - new_value)),
+    }
+}
+
+/// Returns the withdrawal fee on `reserve_tokens` withdrawn for `lp_token_amount`
+/// The first `decayed_lp_tokens` of them are charged `decayed_fee_bps` and the rest `fee_bps`
+pub fn calc_withdrawal_fee(
+    reserve_tokens: u64,
+    lp_token_amount: u64,
+    decayed_lp_tokens: u64,
+    fee_bps: u64,
+    decayed_fee_bps: u64,
+) -> Option<u64> {
+    if lp_token_amount == 0 {
+        return Some(0);
+    }
+
+    let decayed_lp_tokens = decayed_lp_tokens.min(lp_token_amount) as u128;
+    let weighted_bps = (lp_token_amount as u128 - decayed_lp_tokens) * fee_bps as u128
+        + decayed_lp_tokens * decayed_fee_bps as u128;
+
+    (reserve_tokens as u128)
+        .checked_mul(weighted_bps)?
+        .checked_div(lp_token_amount as u128 * ONE_AS_BPS as u128)
+        .and_then(|fee| u64::try_from(fee).ok())
+}
+
+/// Scale of the referral fees accrued per referred reserve token
+pub const REFERRAL_FEE_SCALE: u128 = 1_000_000_000_000_000_000;
+
+/// Returns the referral fees per referred reserve token when `fees` are split
+/// between `total_referred_value` referred reserve tokens, scaled by REFERRAL_FEE_SCALE
+pub fn calc_referral_fees_per_value(fees: u64, total_referred_value: u64) -> Option<u128> {
+    (fees as u128)
+        .checked_mul(REFERRAL_FEE_SCALE)?
+        .checked_div(total_referred_value as u128)
+}
+
+/// Returns the part of `referral_fees` that goes to the referrers, pro rata to the share of
+/// `vault_value` that they referred, the rest goes to the referral fee receiver
+pub fn calc_referrer_fees(
+    referral_fees: u64,
+    total_referred_value: u64,
+    vault_value: u64,
+) -> Option<u64> {
+    if total_referred_value == 0 {
+        return Some(0);
+    }
+    if total_referred_value >= vault_value {
+        return Some(referral_fees);
+    }
+
+    (referral_fees as u128)
+        .checked_mul(total_referred_value as u128)?
+        .checked_div(vault_value as u128)
+        .and_then(|fees| u64::try_from(fees).ok())
+}
+
+/// Returns the referral fees owed on `referred_value` when the fees per referred reserve token
+/// have grown by `fees_per_value_delta` since the last settlement
+pub fn calc_referral_fees_owed(referred_value: u64, fees_per_value_delta: u128) -> Option<u64> {
+    (referred_value as u128)
+        .checked_mul(fees_per_value_delta)
+        .map(|n| n / REFERRAL_FEE_SCALE)
+        .and_then(|fees| u64::try_from(fees).ok())
+}
+
+/// Returns the part of `referred_value` attributed to `lp_tokens` out of `lp_token_amount`
+/// Withdrawing all of the lp tokens releases all of the referred value
+pub fn calc_referred_value_released(
+    referred_value: u64,
+    lp_token_amount: u64,
+    lp_tokens: u64,
+) -> Option<u64> {
+    if lp_tokens >= lp_token_amount {
+        return Some(referred_value);
+    }
+
+    (referred_value as u128)
+        .checked_mul(lp_tokens as u128)?
+        .checked_div(lp_token_amount as u128)
+        .and_then(|released| u64::try_from(released).ok())
 }
 
 #[cfg(test)]
@@ -80,21 +264,78 @@ mod tests {
 
     #[test]
     fn test_reserve_to_lp_initial() {
-        assert_eq!(calc_reserve_to_lp(20, 0, 0), Some(20));
+        assert_eq!(calc_reserve_to_lp(20, 0, 0), Some(20 * VIRTUAL_LP_TOKENS));
     }
 
     #[test]
     fn test_reserve_to_lp() {
-        assert_eq!(calc_reserve_to_lp(100, 100, 100), Some(100));
-        assert_eq!(calc_reserve_to_lp(10, 100, 200), Some(5));
-        assert_eq!(calc_reserve_to_lp(10, 100, 201), Some(4));
+        assert_eq!(calc_reserve_to_lp(100, 199_000, 199), Some(100_000));
+        assert_eq!(calc_reserve_to_lp(10, 99_000, 199), Some(5_000));
+        assert_eq!(calc_reserve_to_lp(10, 99_000, 200), Some(4_975));
+    }
+
+    #[test]
+    fn test_reserve_to_lp_donation() {
+        // First depositor mints lp tokens for 1 reserve token and donates 1000 reserve tokens
+        let attacker_lp = calc_reserve_to_lp(1, 0, 0).unwrap();
+        assert_eq!(attacker_lp, VIRTUAL_LP_TOKENS);
+
+        // The victim only loses a reserve token to rounding
+        let victim_lp = calc_reserve_to_lp(1000, attacker_lp, 1001).unwrap();
+        assert_eq!(victim_lp, 1996);
+        let supply = attacker_lp + victim_lp;
+        assert_eq!(calc_lp_to_reserve(victim_lp, supply, 2001), Some(999));
+
+        // While the attacker loses half of the donation
+        assert_eq!(calc_lp_to_reserve(attacker_lp, supply, 2001), Some(501));
+    }
+
+    #[test]
+    fn test_reserve_to_lp_max_vault_value() {
+        assert!(calc_reserve_to_lp(MAX_VAULT_VALUE, 0, 0).is_some());
+        assert_eq!(calc_reserve_to_lp(MAX_VAULT_VALUE + 2, 0, 0), None);
+
+        // Deposits up to the max vault value keep the lp token supply in a u64
+        let lp_token_supply = calc_reserve_to_lp(MAX_VAULT_VALUE - 100, 0, 0).unwrap();
+        let lp_tokens = calc_reserve_to_lp(100, lp_token_supply, MAX_VAULT_VALUE - 100).unwrap();
+        assert!(lp_token_supply.checked_add(lp_tokens).is_some());
     }
 
     #[test]
     fn test_lp_to_reserve() {
-        assert_eq!(calc_lp_to_reserve(100, 100, 100), Some(100));
-        assert_eq!(calc_lp_to_reserve(10, 100, 200), Some(20));
-        assert_eq!(calc_lp_to_reserve(10, 101, 200), Some(19));
+        assert_eq!(calc_lp_to_reserve(100_000, 199_000, 199), Some(100));
+        assert_eq!(calc_lp_to_reserve(10_000, 99_000, 199), Some(20));
+        assert_eq!(calc_lp_to_reserve(10_000, 100_000, 199), Some(19));
+    }
+
+    #[test]
+    fn test_share_price() {
+        let initial_share_price = SHARE_PRICE_SCALE / VIRTUAL_LP_TOKENS;
+        assert_eq!(calc_share_price(0, 0), Some(initial_share_price));
+        assert_eq!(calc_share_price(99_000, 199), Some(2 * initial_share_price));
+        assert_eq!(calc_share_price(3_000, 0), Some(initial_share_price / 4));
+    }
+
+    #[test]
+    fn test_profit_above_high_water_mark() {
+        let hwm = 2 * SHARE_PRICE_SCALE;
+        assert_eq!(calc_profit_above_high_water_mark(hwm, hwm, 100), Some(0));
+        assert_eq!(calc_profit_above_high_water_mark(SHARE_PRICE_SCALE, hwm, 100), Some(0));
+        assert_eq!(
+            calc_profit_above_high_water_mark(3 * SHARE_PRICE_SCALE, hwm, 100),
+            Some(100)
+        );
+    }
+
+    #[test]
+    fn test_entry_share_price() {
+        assert_eq!(calc_entry_share_price(0, 0, 0, 0), Some(0));
+        assert_eq!(calc_entry_share_price(0, 0, 100, 100), Some(SHARE_PRICE_SCALE));
+        // 100 lp tokens at 1 and 100 at 2 average out at 1.5
+        assert_eq!(
+            calc_entry_share_price(SHARE_PRICE_SCALE, 100, 200, 100),
+            Some(3 * SHARE_PRICE_SCALE / 2)
+        );
     }
 
     #[test]
@@ -105,7 +346,117 @@ mod tests {
 
     #[test]
     fn test_mgmt_fees() {
-        assert_eq!(calc_mgmt_fees(1261440000, 1000, 100).unwrap(), 200);
+        assert_eq!(calc_mgmt_fees(630720000, 1000, 100, 0).unwrap(), (200, 0));
+    }
+
+    #[test]
+    fn test_mgmt_fees_remainder() {
+        // A tenth of a reserve token is carried over instead of truncated
+        let denominator = ONE_AS_BPS * SECONDS_PER_YEAR;
+        let (fees, remainder) = calc_mgmt_fees(31536000, 1000, 1, 0).unwrap();
+        assert_eq!((fees, remainder), (0, denominator / 10));
+
+        let (fees, remainder) = calc_mgmt_fees(31536000, 1000, 9, remainder).unwrap();
+        assert_eq!((fees, remainder), (1, 0));
+    }
+
+    #[test]
+    fn test_linear_decay() {
+        assert_eq!(calc_linear_decay(100, 0, 1000), 100);
+        assert_eq!(calc_linear_decay(100, 1000, 0), 100);
+        assert_eq!(calc_linear_decay(100, 1000, 250), 75);
+        assert_eq!(calc_linear_decay(100, 1000, 1000), 0);
+        assert_eq!(calc_linear_decay(100, 1000, 5000), 0);
+        assert_eq!(calc_linear_decay(u64::MAX, u64::MAX, 0), u64::MAX);
+    }
+
+    #[test]
+    fn test_locked_profit() {
+        // A gain of 1000 unlocks linearly over 100 slots
+        let locked_profit = calc_locked_profit_after_update(0, 10000, 11000).unwrap();
+        assert_eq!(calc_locked_profit(locked_profit, 100, 0), 1000);
+        assert_eq!(calc_locked_profit(locked_profit, 100, 50), 500);
+
+        // A second gain 50 slots later is locked on top of what is still locked
+        let locked_profit = calc_locked_profit(locked_profit, 100, 50);
+        let locked_profit = calc_locked_profit_after_update(locked_profit, 11000, 11200).unwrap();
+        assert_eq!(locked_profit, 700);
+        assert_eq!(calc_locked_profit(locked_profit, 100, 50), 350);
+        assert_eq!(calc_locked_profit(locked_profit, 100, 100), 0);
+        assert_eq!(calc_locked_profit(locked_profit, 100, 150), 0);
+    }
+
+    #[test]
+    fn test_locked_profit_loss() {
+        // A loss is taken out of the profit that is still locked first
+        assert_eq!(
+            calc_locked_profit_after_update(500, 11000, 10800),
+            Some(300)
+        );
+        // and only the rest of it out of the share price
+        assert_eq!(calc_locked_profit_after_update(500, 11000, 10000), Some(0));
+        assert_eq!(calc_locked_profit_after_update(0, 11000, 10000), Some(0));
+    }
+
+    #[test]
+    fn test_locked_profit_no_unlock_slots() {
+        let locked_profit = calc_locked_profit_after_update(0, 10000, 11000).unwrap();
+        assert_eq!(calc_locked_profit(locked_profit, 0, 0), 0);
+        assert_eq!(calc_locked_profit(locked_profit, 0, 50), 0);
+    }
+
+    #[test]
+    fn test_withdrawal_fee() {
+        assert_eq!(calc_withdrawal_fee(10000, 0, 0, 100, 0), Some(0));
+        assert_eq!(calc_withdrawal_fee(10000, 100, 0, 100, 0), Some(100));
+        assert_eq!(calc_withdrawal_fee(10000, 100, 100, 100, 50), Some(50));
+        // Decayed lp tokens above the withdrawn amount do not lower the fee further
+        assert_eq!(calc_withdrawal_fee(10000, 100, 500, 100, 50), Some(50));
+        // Half of the lp tokens are charged the full fee
+        assert_eq!(calc_withdrawal_fee(10000, 100, 50, 100, 0), Some(50));
+    }
+
+    #[test]
+    fn test_withdrawal_fee_transferred_in() {
+        // A position that tracks 100 decayed lp tokens only covers that many of the 300
+        // withdrawn, whichever deposit minted them, the 200 transferred in on top pay in full
+        assert_eq!(calc_withdrawal_fee(30000, 300, 100, 100, 0), Some(200));
+        // Lp tokens transferred in up to the tracked amount get the decayed fee
+        assert_eq!(calc_withdrawal_fee(10000, 100, 100, 100, 0), Some(0));
+    }
+
+    #[test]
+    fn test_referral_fees() {
+        assert_eq!(calc_referral_fees_per_value(100, 0), None);
+
+        // Referrers with 300 and 100 referred reserve tokens split 100 in fees 3:1
+        let fees_per_value = calc_referral_fees_per_value(100, 400).unwrap();
+        assert_eq!(calc_referral_fees_owed(300, fees_per_value), Some(75));
+        assert_eq!(calc_referral_fees_owed(100, fees_per_value), Some(25));
+
+        // Rounding down never owes more than the fees that were split
+        let fees_per_value = calc_referral_fees_per_value(10, 3).unwrap();
+        assert_eq!(calc_referral_fees_owed(1, fees_per_value), Some(3));
+        assert_eq!(calc_referral_fees_owed(2, fees_per_value), Some(6));
+    }
+
+    #[test]
+    fn test_referrer_fees() {
+        assert_eq!(calc_referrer_fees(100, 0, 1000), Some(0));
+        // Referred deposits make up a quarter of the vault value
+        assert_eq!(calc_referrer_fees(100, 250, 1000), Some(25));
+        // Losses can leave the referred value above the vault value
+        assert_eq!(calc_referrer_fees(100, 1500, 1000), Some(100));
+        assert_eq!(calc_referrer_fees(100, 0, 0), Some(0));
+    }
+
+    #[test]
+    fn test_referred_value_released() {
+        assert_eq!(calc_referred_value_released(1000, 400, 100), Some(250));
+        assert_eq!(calc_referred_value_released(1000, 400, 400), Some(1000));
+        // Untracked lp tokens on top of the position do not release more than it referred
+        assert_eq!(calc_referred_value_released(1000, 400, 500), Some(1000));
+        assert_eq!(calc_referred_value_released(10, 3, 1), Some(3));
     }
 
     #[test]
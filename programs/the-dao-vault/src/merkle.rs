@@ -0,0 +1,40 @@
+use anchor_lang::{prelude::*, solana_program::keccak::hashv};
+
+/// Returns the leaf of a (user, max deposit) pair in the deposit allowlist
+pub fn allowlist_leaf(user: &Pubkey, max_deposit: u64) -> [u8; 32] {
+    hashv(&[user.as_ref(), &max_deposit.to_le_bytes()]).to_bytes()
+}
+
+/// Returns if `proof` leads from `leaf` to `root`
+/// Pairs of nodes are hashed in sorted order, so proofs do not say which side a node is on
+pub fn verify_proof(proof: &[[u8; 32]], root: &[u8; 32], leaf: [u8; 32]) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        if node <= *sibling {
+            hashv(&[&node, sibling]).to_bytes()
+        } else {
+            hashv(&[sibling, &node]).to_bytes()
+        }
+    });
+    computed == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_proof() {
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let alice_leaf = allowlist_leaf(&alice, 100);
+        let bob_leaf = allowlist_leaf(&bob, 200);
+        let root = match alice_leaf <= bob_leaf {
+            true => hashv(&[&alice_leaf, &bob_leaf]).to_bytes(),
+            false => hashv(&[&bob_leaf, &alice_leaf]).to_bytes(),
+        };
+
+        assert!(verify_proof(&[bob_leaf], &root, alice_leaf));
+        assert!(verify_proof(&[alice_leaf], &root, bob_leaf));
+        assert!(!verify_proof(&[bob_leaf], &root, allowlist_leaf(&alice, 200)));
+        assert!(!verify_proof(&[], &root, alice_leaf));
+    }
+}
@@ -1,13 +1,21 @@
 #[cfg(test)]
 use mockall::*;
 
-use anchor_lang::prelude::*;
+use std::ops::Deref;
+
+use anchor_lang::{prelude::*, solana_program::program_pack::Pack};
+use boolinator::Boolinator;
+use port_anchor_adaptor::PortReserve as PortReserveAccount;
 use port_variable_rate_lending_instructions::state::Reserve as PortReserve;
 use solana_maths::{Rate, TryMul};
+use spl_token_lending::state::Reserve as TokenLendingReserve;
 use strum_macros::{EnumCount, EnumIter};
 
-use crate::adapters::solend::SolendReserve;
-// use crate::reserves::Provider;
+use crate::{
+    adapters::{jet_program::Reserve as JetReserve, solend::SolendReserve},
+    errors::ErrorCode,
+    state::YieldSource,
+};
 
 #[derive(
     Clone,
@@ -23,35 +31,19 @@ use crate::adapters::solend::SolendReserve;
     AnchorSerialize,
     AnchorDeserialize,
 )]
+#[repr(u8)]
 pub enum Provider {
     Solend = 0,
     Port,
+    Jet,
+    /// Any fork of spl-token-lending, identified by the lending program of the yield source
+    TokenLending,
 }
 
-#[macro_export]
-macro_rules! impl_provider_index {
-    ($t: ty, $o: ty) => {
-        use crate::reserves::Provider;
-        impl core::ops::Index<Provider> for $t {
-            type Output = $o;
-
-            fn index(&self, provider: Provider) -> &Self::Output {
-                match provider {
-                    Provider::Solend => &self.solend,
-                    Provider::Port => &self.port,
-                }
-            }
-        }
-
-        impl core::ops::IndexMut<Provider> for $t {
-            fn index_mut(&mut self, provider: Provider) -> &mut Self::Output {
-                match provider {
-                    Provider::Solend => &mut self.solend,
-                    Provider::Port => &mut self.port,
-                }
-            }
-        }
-    };
+impl Default for Provider {
+    fn default() -> Self {
+        Provider::Solend
+    }
 }
 
 #[cfg_attr(test, automock)]
@@ -85,6 +77,41 @@ where
 pub enum Reserves {
     Solend(SolendReserve),
     Port(PortReserve),
+    Jet(Box<JetReserve>),
+    TokenLending(TokenLendingReserve),
+}
+
+impl Reserves {
+    /// Deserializes the reserve account of a yield source using its provider
+    pub fn try_from_account(yield_source: &YieldSource, account: &AccountInfo) -> Result<Self> {
+        (account.key() == yield_source.reserve && account.owner == &yield_source.lending_program)
+            .ok_or(ErrorCode::InvalidAccount)?;
+
+        match yield_source.provider {
+            Provider::Solend => {
+                let data = account.try_borrow_data()?;
+                Ok(Reserves::Solend(SolendReserve::try_deserialize(&mut &data[..])?))
+            }
+            Provider::Port => {
+                let data = account.try_borrow_data()?;
+                let reserve = PortReserveAccount::try_deserialize(&mut &data[..])?;
+                Ok(Reserves::Port(reserve.deref().clone()))
+            }
+            Provider::Jet => {
+                let reserve = AccountLoader::<JetReserve>::try_from(account)?;
+                let reserve = Box::new(*reserve.load()?);
+                Ok(Reserves::Jet(reserve))
+            }
+            Provider::TokenLending => {
+                // Owner is already checked against the lending program of the yield source
+                let data = account.try_borrow_data()?;
+                match TokenLendingReserve::unpack(&data) {
+                    Ok(reserve) => Ok(Reserves::TokenLending(reserve)),
+                    Err(err) => Err(err.into()),
+                }
+            }
+        }
+    }
 }
 
 impl<'a> ReserveAccessor for Reserves {
@@ -92,6 +119,8 @@ impl<'a> ReserveAccessor for Reserves {
         match self {
             Reserves::Solend(reserve) => reserve.utilization_rate(),
             Reserves::Port(reserve) => reserve.utilization_rate(),
+            Reserves::Jet(reserve) => reserve.utilization_rate(),
+            Reserves::TokenLending(reserve) => reserve.utilization_rate(),
         }
     }
 
@@ -99,6 +128,8 @@ impl<'a> ReserveAccessor for Reserves {
         match self {
             Reserves::Solend(reserve) => reserve.borrow_rate(),
             Reserves::Port(reserve) => reserve.borrow_rate(),
+            Reserves::Jet(reserve) => reserve.borrow_rate(),
+            Reserves::TokenLending(reserve) => reserve.borrow_rate(),
         }
     }
 
@@ -106,6 +137,8 @@ impl<'a> ReserveAccessor for Reserves {
         match self {
             Reserves::Solend(reserve) => reserve.reserve_with_deposit(allocation),
             Reserves::Port(reserve) => reserve.reserve_with_deposit(allocation),
+            Reserves::Jet(reserve) => reserve.reserve_with_deposit(allocation),
+            Reserves::TokenLending(reserve) => reserve.reserve_with_deposit(allocation),
         }
     }
 }
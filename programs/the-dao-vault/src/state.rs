@@ -1,7 +1,8 @@
 use anchor_lang::prelude::*;
+use anchor_spl::associated_token::get_associated_token_address;
 use std::cmp::Ordering;
 
-use strum::IntoEnumIterator;
+use boolinator::Boolinator;
 #[cfg(test)]
 use type_layout::TypeLayout;
 
@@ -10,13 +11,25 @@ use jet_proto_proc_macros::assert_size;
 use crate::{
     asset_container::AssetContainer,
     errors::ErrorCode,
-    impl_provider_index,
     instructions::VaultConfigArg,
-    math::{calc_carry_fees, calc_mgmt_fees},
+    math::{
+        calc_carry_fees, calc_entry_share_price, calc_linear_decay, calc_locked_profit,
+        calc_locked_profit_after_update, calc_mgmt_fees, calc_profit_above_high_water_mark,
+        calc_referral_fees_owed, calc_referral_fees_per_value, calc_referred_value_released,
+        calc_share_price, MAX_VAULT_VALUE, ONE_AS_BPS,
+    },
+    merkle::{allowlist_leaf, verify_proof},
+    reserves::Provider,
 };
 
+/// Maximum number of yield sources that a vault can allocate to
+pub const MAX_YIELD_SOURCES: usize = 8;
+
+/// Maximum number of accounts that the non-referral fees are split between
+pub const MAX_FEE_RECIPIENTS: usize = 4;
+
 // use crate::
-#[assert_size(768)]
+#[assert_size(2192)]
 #[account]
 #[repr(C, align(8))]
 #[derive(Debug)]
@@ -32,41 +45,84 @@ pub struct Vault {
 
     pub authority_bump: [u8; 1],
 
-    pub solend_reserve: Pubkey,
+    pub vault_reserve_token: Pubkey,
 
-    pub port_reserve: Pubkey,
+    pub lp_token_mint: Pubkey,
 
-    pub jet_reserve: Pubkey,
+    pub reserve_token_mint: Pubkey,
 
-    pub vault_reserve_token: Pubkey,
+    pub referral_fee_receiver: Pubkey,
 
-    pub vault_solend_lp_token: Pubkey,
+    /// Owner of the referral fee receiver, whose associated token accounts receive
+    /// the referral fees that are not paid in lp tokens
+    pub referral_fee_owner: Pubkey,
 
-    pub vault_port_lp_token: Pubkey,
+    bitflags: u32,
 
-    pub vault_jet_lp_token: Pubkey,
+    pub value: SlotTrackecValue,
 
-    pub lp_token_mint: Pubkey,
+    pub config: VaultConfig,
 
-    pub reserve_token_mint: Pubkey,
+    /// Lending markets that the vault can allocate to
+    /// Unused slots have a default reserve pubkey
+    pub yield_sources: [YieldSource; MAX_YIELD_SOURCES],
 
-    pub fee_receiver: Pubkey,
+    /// Account proposed by the owner to take over the vault
+    /// Only becomes the owner once it accepts
+    pub pending_owner: Pubkey,
 
-    pub referral_fee_receiver: Pubkey,
+    /// Highest lp token share price that carry fees have been charged up to
+    /// Scaled by SHARE_PRICE_SCALE, zero until the first fee collection sets it
+    pub high_water_mark: u64,
 
-    bitflags: u32,
+    /// Slot at which fees were last collected
+    pub last_fee_collection: LastUpdate,
 
-    pub value: SlotTrackecValue,
+    /// Management fee numerator that did not add up to a whole reserve token
+    /// Carried over to the next fee collection
+    pub mgmt_fee_remainder: u64,
 
-    pub target_allocations: Allocations,
+    /// Accounts that the non-referral fees are split between
+    /// Unused slots have a default token account pubkey
+    pub fee_recipients: [FeeRecipient; MAX_FEE_RECIPIENTS],
 
-    pub config: VaultConfig,
+    /// Referral fees accrued per referred reserve token, scaled by REFERRAL_FEE_SCALE
+    /// Indexed by the fee denomination that they were paid in
+    pub referral_fees_per_value: [u128; 2],
+
+    /// Reserve tokens deposited with a referrer
+    /// Referral fees go to the referral fee owner while this is zero
+    pub total_referred_value: u64,
+
+    /// Merkle root of the (user, max deposit) pairs that can deposit while the allowlist is enabled
+    pub deposit_allowlist_root: [u8; 32],
+
+    /// Whether deposits are limited to the users in the allowlist
+    pub deposit_allowlist_enabled: bool,
+
+    /// Index of the next withdrawal request to fulfil
+    pub withdrawal_queue_head: u64,
+
+    /// Index that the next withdrawal request gets
+    pub withdrawal_queue_tail: u64,
 
-    pub actual_allocations: Allocations,
+    /// Lp tokens escrowed by withdrawal requests that are priced at fulfilment
+    pub queued_lp_tokens: u64,
 
-    // 8 * 23 = 184
+    /// Reserve tokens owed to withdrawal requests that were priced at request time
+    /// Excluded from the vault value
+    pub queued_withdrawal_value: u64,
+
+    /// Realized gains that are still being released into the share price
+    /// as of `locked_profit_slot`
+    pub locked_profit: u64,
+
+    /// Slot at which the locked profit was last updated
+    pub locked_profit_slot: u64,
+
+    // 8 * 2 = 16
     /// Reserved spacce for future upgrades
-    _reserved: [u64; 14],
+    _reserved: [u64; 2],
 }
 
 impl Vault {
@@ -81,31 +137,360 @@ impl Vault {
         Ok(())
     }
 
-    pub fn calculate_fees(&self, new_vault_value: u64, slot: u64) -> Result<u64> {
-        let vault_value_diff = new_vault_value.saturating_sub(self.value.value);
-        let slots_elapsed = self.value.last_update.slots_elapsed(slot)?;
+    /// Management fees accrue over the seconds since the last fee collection
+    /// Carry is only charged on share price gains above the high-water mark,
+    /// so that recovering from a drawdown is not charged twice
+    ///
+    /// Returns the total fees and the management fee remainder to store for the next collection
+    pub fn calculate_fees(
+        &self,
+        new_vault_value: u64,
+        lp_token_supply: u64,
+        unix_timestamp: i64,
+    ) -> Result<(u64, u64)> {
+        let share_price =
+            calc_share_price(lp_token_supply, new_vault_value).ok_or(ErrorCode::MathError)?;
+        let profit = match self.high_water_mark {
+            // Mark is set on the first fee collection without charging carry
+            0 => 0,
+            high_water_mark => {
+                calc_profit_above_high_water_mark(share_price, high_water_mark, lp_token_supply)
+                    .ok_or(ErrorCode::MathError)?
+            }
+        };
+        let seconds_elapsed = self.last_fee_collection.seconds_elapsed(unix_timestamp)?;
 
-        let carry = calc_carry_fees(vault_value_diff, self.config.fee_carry_bps as u64)?;
-        let mgmt = calc_mgmt_fees(
+        let carry = calc_carry_fees(profit, self.config.fee_carry_bps as u64)?;
+        let (mgmt, mgmt_fee_remainder) = calc_mgmt_fees(
             new_vault_value,
             self.config.fee_mgmt_bps as u64,
-            slots_elapsed,
+            seconds_elapsed,
+            self.mgmt_fee_remainder,
         )?;
 
         #[cfg(feature = "debug")]
         {
-            msg!("Slots elapsed: {}", slots_elapsed);
+            msg!("Seconds elapsed: {}", seconds_elapsed);
             msg!("New vault value: {}", new_vault_value);
             msg!("Old vault value: {}", self.value.value);
+            msg!("Share price: {}", share_price);
+            msg!("High-water mark: {}", self.high_water_mark);
             msg!("Carry fee: {}", carry);
             msg!("Mgmt fee: {}", mgmt);
         }
 
         carry
             .checked_add(mgmt)
+            .map(|total| (total, mgmt_fee_remainder))
             .ok_or_else(|| ErrorCode::OverflowError.into())
     }
 
+    /// Raises the high-water mark to the current share price
+    /// Returns the new mark if it was raised
+    pub fn update_high_water_mark(
+        &mut self,
+        vault_value: u64,
+        lp_token_supply: u64,
+    ) -> Result<Option<u64>> {
+        let share_price =
+            calc_share_price(lp_token_supply, vault_value).ok_or(ErrorCode::MathError)?;
+
+        if share_price <= self.high_water_mark {
+            return Ok(None);
+        }
+
+        self.high_water_mark = share_price;
+        Ok(Some(share_price))
+    }
+
+    /// Returns the part of the locked profit that is not released yet in `slot`
+    pub fn locked_profit(&self, slot: u64) -> Result<u64> {
+        let slots_elapsed = slot
+            .checked_sub(self.locked_profit_slot)
+            .ok_or(ErrorCode::MathError)?;
+
+        Ok(calc_locked_profit(
+            self.locked_profit,
+            self.config.profit_unlock_slots,
+            slots_elapsed,
+        ))
+    }
+
+    /// Carries the locked profit over to `slot` at the current unlock rate,
+    /// so that the profit still locked then unlocks over the full unlock slots from there
+    pub fn settle_locked_profit(&mut self, slot: u64) -> Result<()> {
+        self.locked_profit = self.locked_profit(slot)?;
+        self.locked_profit_slot = slot;
+        Ok(())
+    }
+
+    /// Returns the vault value without the profit that is still locked in `slot`
+    /// Lp tokens are priced against this value, so that deposits made right before a gain
+    /// do not capture it
+    pub fn unlocked_value(&self, slot: u64) -> Result<u64> {
+        Ok(self.value.value.saturating_sub(self.locked_profit(slot)?))
+    }
+
+    /// Sets a newly refreshed vault value
+    /// Gains since the last value are locked on top of the profit that is still locked,
+    /// losses are taken out of the locked profit first
+    pub fn update_value(&mut self, vault_value: u64, clock: &Clock) -> Result<()> {
+        self.settle_locked_profit(clock.slot)?;
+        self.locked_profit =
+            calc_locked_profit_after_update(self.locked_profit, self.value.value, vault_value)
+                .ok_or(ErrorCode::OverflowError)?;
+        self.value.update(vault_value, clock);
+        Ok(())
+    }
+
+    /// Reserve tokens that rebalance keeps idle out of the vault value
+    pub fn liquidity_buffer(&self) -> Result<u64> {
+        self.value
+            .value
+            .checked_mul(self.config.buffer_pct as u64)
+            .map(|n| n / 100)
+            .ok_or_else(|| ErrorCode::MathError.into())
+    }
+
+    /// Reserve tokens that reconcile does not deposit out of the vault,
+    /// the liquidity buffer and what queued withdrawal requests are owed
+    pub fn idle_reserve_floor(&self) -> Result<u64> {
+        self.liquidity_buffer()?
+            .checked_add(self.queued_withdrawal_value)
+            .ok_or_else(|| ErrorCode::OverflowError.into())
+    }
+
+    /// Returns the lp tokens that get the decayed withdrawal fee and the decayed fee in bps
+    /// Only lp tokens tracked by the user position get the fee decayed since its last deposit
+    ///
+    /// Lp tokens are fungible, so the position covers an amount of them rather than the ones
+    /// its deposits minted: up to that amount of lp tokens transferred in from a fresh deposit
+    /// get the decayed fee too, which takes keeping as much in the vault over the decay period
+    pub fn decayed_withdrawal_fee(
+        &self,
+        user_position: Option<&UserPosition>,
+        slot: u64,
+    ) -> Result<(u64, u64)> {
+        match user_position {
+            Some(user_position) => Ok((
+                user_position.lp_token_amount,
+                self.decayed_withdrawal_fee_bps(user_position.slots_since_deposit(slot)?),
+            )),
+            None => Ok((0, 0)),
+        }
+    }
+
+    /// Associated reserve token account of the first fee recipient,
+    /// which receives the withdrawal fees when they do not stay in the vault
+    pub fn withdrawal_fee_receiver(&self) -> Pubkey {
+        get_associated_token_address(&self.fee_recipients[0].owner, &self.reserve_token_mint)
+    }
+
+    /// Returns the withdrawal fee in bps for lp tokens deposited `slots_since_deposit` slots ago
+    /// The fee decays linearly to zero over the configured decay slots
+    pub fn decayed_withdrawal_fee_bps(&self, slots_since_deposit: u64) -> u64 {
+        calc_linear_decay(
+            self.config.withdrawal_fee_bps as u64,
+            self.config.withdrawal_fee_decay_slots as u64,
+            slots_since_deposit,
+        )
+    }
+
+    /// Splits referral fees paid in `fee_denomination` between the referrers
+    /// by their referred value, referrers claim their share after settling
+    pub fn accrue_referral_fees(
+        &mut self,
+        fee_denomination: FeeDenomination,
+        referral_fees: u64,
+    ) -> Result<()> {
+        let fees_per_value = calc_referral_fees_per_value(referral_fees, self.total_referred_value)
+            .ok_or(ErrorCode::MathError)?;
+        let accrued = &mut self.referral_fees_per_value[fee_denomination as usize];
+        *accrued = accrued
+            .checked_add(fees_per_value)
+            .ok_or(ErrorCode::OverflowError)?;
+        Ok(())
+    }
+
+    /// Credits a referrer with reserve tokens deposited through them
+    pub fn add_referred_value(&mut self, referrer: &mut Referrer, amount: u64) -> Result<()> {
+        referrer.settle(self)?;
+        referrer.referred_value = referrer
+            .referred_value
+            .checked_add(amount)
+            .ok_or(ErrorCode::OverflowError)?;
+        self.total_referred_value = self
+            .total_referred_value
+            .checked_add(amount)
+            .ok_or(ErrorCode::OverflowError)?;
+        Ok(())
+    }
+
+    /// Takes the value that `user_position` referred off its referrer as `lp_tokens` leave it,
+    /// pro rata to the lp tokens that the position tracks, and returns the value taken off
+    /// Must be called before the lp tokens are taken off the position
+    pub fn release_referred_value(
+        &mut self,
+        vault_key: &Pubkey,
+        user_position: &mut UserPosition,
+        accounts: &[AccountInfo],
+        lp_tokens: u64,
+    ) -> Result<u64> {
+        if user_position.referred_value == 0 {
+            return Ok(0);
+        }
+
+        let released = calc_referred_value_released(
+            user_position.referred_value,
+            user_position.lp_token_amount,
+            lp_tokens,
+        )
+        .ok_or(ErrorCode::MathError)?;
+
+        let mut referrer = Referrer::from_accounts(accounts, vault_key, &user_position.referrer)?
+            .ok_or(ErrorCode::InsufficientAccounts)?;
+        referrer.settle(self)?;
+        referrer.referred_value = referrer
+            .referred_value
+            .checked_sub(released)
+            .ok_or(ErrorCode::MathError)?;
+        self.total_referred_value = self
+            .total_referred_value
+            .checked_sub(released)
+            .ok_or(ErrorCode::MathError)?;
+        user_position.referred_value = user_position
+            .referred_value
+            .checked_sub(released)
+            .ok_or(ErrorCode::MathError)?;
+        referrer.exit(&crate::ID)?;
+        Ok(released)
+    }
+
+    /// Credits the referrer of `user_position` back with `amount` of referred value
+    /// that a cancelled withdrawal request had released
+    pub fn restore_referred_value(
+        &mut self,
+        vault_key: &Pubkey,
+        user_position: &mut UserPosition,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let mut referrer = Referrer::from_accounts(accounts, vault_key, &user_position.referrer)?
+            .ok_or(ErrorCode::InsufficientAccounts)?;
+        self.add_referred_value(&mut referrer, amount)?;
+        user_position.referred_value = user_position
+            .referred_value
+            .checked_add(amount)
+            .ok_or(ErrorCode::OverflowError)?;
+        referrer.exit(&crate::ID)
+    }
+
+    /// Returns if the allowlist has a pair of `user` and `max_deposit` that `proof` leads to
+    pub fn is_deposit_allowlisted(
+        &self,
+        user: &Pubkey,
+        max_deposit: u64,
+        proof: &[[u8; 32]],
+    ) -> bool {
+        verify_proof(
+            proof,
+            &self.deposit_allowlist_root,
+            allowlist_leaf(user, max_deposit),
+        )
+    }
+
+    /// Returns the index of the registered yield source that the given accounts belong to
+    pub fn yield_source_index(
+        &self,
+        provider: Provider,
+        reserve: Pubkey,
+        lp_token: Pubkey,
+    ) -> Result<usize> {
+        self.yield_sources
+            .iter()
+            .position(|yield_source| {
+                yield_source.is_registered()
+                    && yield_source.provider == provider
+                    && yield_source.reserve == reserve
+                    && yield_source.lp_token == lp_token
+            })
+            .ok_or_else(|| ErrorCode::InvalidAccount.into())
+    }
+
+    /// Registers a new enabled yield source in the first free slot
+    pub fn add_yield_source(
+        &mut self,
+        provider: Provider,
+        lending_program: Pubkey,
+        reserve: Pubkey,
+        lp_token: Pubkey,
+    ) -> Result<usize> {
+        (!self
+            .yield_sources
+            .iter()
+            .any(|yield_source| yield_source.is_registered() && yield_source.reserve == reserve))
+        .ok_or(ErrorCode::YieldSourceAlreadyRegistered)?;
+
+        let index = self
+            .yield_sources
+            .iter()
+            .position(|yield_source| !yield_source.is_registered())
+            .ok_or(ErrorCode::YieldSourceLimitReached)?;
+
+        self.yield_sources[index] = YieldSource::new(provider, lending_program, reserve, lp_token);
+        Ok(index)
+    }
+
+    /// Sets the target allocation of every registered yield source
+    /// Yield sources missing from the allocations are targeted to zero so that reconcile winds them down
+    pub fn set_target_allocations(&mut self, allocations: &AssetContainer<u64>, clock: &Clock) {
+        for (index, yield_source) in self.yield_sources.iter_mut().enumerate() {
+            if yield_source.is_registered() {
+                let allocation = allocations.get(index).copied().unwrap_or(0);
+                yield_source.target_allocation.update(allocation, clock);
+            }
+        }
+    }
+
+    /// Replaces the fee recipients
+    /// Shares have to be non-zero and add up to 100% across distinct token accounts
+    pub fn set_fee_recipients(&mut self, recipients: &[FeeRecipient]) -> Result<()> {
+        let total_bps = recipients
+            .iter()
+            .try_fold(0_u64, |acc, recipient| {
+                (recipient.is_registered() && recipient.share_bps > 0)
+                    .as_some(acc + recipient.share_bps as u64)
+            })
+            .ok_or(ErrorCode::InvalidFeeRecipients)?;
+
+        let distinct = recipients.iter().enumerate().all(|(index, recipient)| {
+            recipients[..index]
+                .iter()
+                .all(|other| other.token_account != recipient.token_account)
+        });
+
+        (!recipients.is_empty()
+            && recipients.len() <= MAX_FEE_RECIPIENTS
+            && total_bps == ONE_AS_BPS
+            && distinct)
+            .ok_or(ErrorCode::InvalidFeeRecipients)?;
+
+        self.fee_recipients = [FeeRecipient::default(); MAX_FEE_RECIPIENTS];
+        self.fee_recipients[..recipients.len()].copy_from_slice(recipients);
+        Ok(())
+    }
+
+    /// Returns the fee recipients in use
+    pub fn registered_fee_recipients(&self) -> impl Iterator<Item = &FeeRecipient> {
+        self.fee_recipients
+            .iter()
+            .filter(|recipient| recipient.is_registered())
+    }
+
     pub fn authority_seeds(&self) -> [&[u8]; 3] {
         [
             self.authority_seed.as_ref(),
@@ -115,11 +500,12 @@ impl Vault {
     }
 }
 
-#[assert_size(aligns, 32)]
+#[assert_size(aligns, 40)]
 #[repr(C, align(8))]
 #[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug)]
 #[cfg_attr(test, derive(TypeLayout))]
 pub struct VaultConfig {
+    /// Never above MAX_VAULT_VALUE
     pub deposit_cap: u64,
     pub fee_carry_bps: u32,
     pub fee_mgmt_bps: u32,
@@ -127,7 +513,20 @@ pub struct VaultConfig {
     pub allocation_cap_pct: u8,
     pub rebalance_mode: RebalanceMode,
     pub strategy_type: StrategyType,
-    _padding: [u32; 3],
+    pub fee_denomination: FeeDenomination,
+    pub withdrawal_fee_destination: WithdrawalFeeDestination,
+    /// Fee charged on withdraws in bps, zero turns withdrawal fees off
+    pub withdrawal_fee_bps: u16,
+    /// Slots after a user's last deposit over which the withdrawal fee decays to zero
+    /// Zero charges the full fee regardless of when the user deposited
+    pub withdrawal_fee_decay_slots: u32,
+    pub withdrawal_price_policy: WithdrawalPricePolicy,
+    /// Percentage of the vault value that rebalance keeps idle for withdraws
+    pub buffer_pct: u8,
+    _padding: [u8; 2],
+    /// Slots over which gains booked by a refresh are released into the share price
+    /// Zero releases them immediately
+    pub profit_unlock_slots: u64,
 }
 
 impl VaultConfig {
@@ -142,26 +541,44 @@ impl VaultConfig {
             return Err(ErrorCode::InvalidFeeConfig.into());
         }
 
+        // Fee cannot be over 100%
+        if config.withdrawal_fee_bps as u64 > ONE_AS_BPS {
+            return Err(ErrorCode::InvalidFeeConfig.into());
+        }
+
         // Referral percentage cannot be over 50%
         if config.referral_fee_pct > 50 {
             return Err(ErrorCode::InvalidReferralFeeConfig.into());
         }
 
-        // compute the lower limits of the cap using number of yield source
-        // TODO get this from MAX const in Chris's changes
-        if !(34..100).contains(&config.allocation_cap_pct) {
+        // Buffer cannot be over 100%
+        if config.buffer_pct > 100 {
+            return Err(ErrorCode::InvalidBufferConfig.into());
+        }
+
+        // Allocation cap has to let the vault allocate everything when all yield sources are used
+        let min_allocation_cap_pct = (100 + MAX_YIELD_SOURCES - 1) / MAX_YIELD_SOURCES;
+        if !(min_allocation_cap_pct..100).contains(&(config.allocation_cap_pct as usize)) {
             return Err(ErrorCode::InvalidAloocationCap.into());
         }
 
         Ok(Self {
-            deposit_cap: config.deposit_cap,
+            // Deposits past the max vault value would overflow the lp token supply
+            deposit_cap: config.deposit_cap.min(MAX_VAULT_VALUE),
             fee_carry_bps: config.fee_carry_bps,
             fee_mgmt_bps: config.fee_mgmt_bps,
             referral_fee_pct: config.referral_fee_pct,
             allocation_cap_pct: config.allocation_cap_pct,
             rebalance_mode: config.rebalance_mode,
             strategy_type: config.strategy_type,
-            _padding: [0; 3],
+            fee_denomination: config.fee_denomination,
+            withdrawal_fee_destination: config.withdrawal_fee_destination,
+            withdrawal_fee_bps: config.withdrawal_fee_bps,
+            withdrawal_fee_decay_slots: config.withdrawal_fee_decay_slots,
+            withdrawal_price_policy: config.withdrawal_price_policy,
+            buffer_pct: config.buffer_pct,
+            _padding: [0; 2],
+            profit_unlock_slots: config.profit_unlock_slots,
         })
     }
 }
@@ -180,31 +597,390 @@ pub enum StrategyType {
     EqualAllocation,
 }
 
+/// Token that fees are paid in
+#[repr(u8)]
+#[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, PartialEq)]
+pub enum FeeDenomination {
+    /// Newly minted vault lp tokens
+    LpToken,
+    /// Reserve tokens transferred out of the vault
+    ReserveToken,
+}
+
+/// Where withdrawal fees go
+#[repr(u8)]
+#[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, PartialEq)]
+pub enum WithdrawalFeeDestination {
+    /// Left in the vault for the remaining lp token holders
+    Vault,
+    /// Transferred to the reserve token account of the first fee recipient
+    FeeReceiver,
+}
+
+/// When queued withdrawal requests are priced
+#[repr(u8)]
+#[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, PartialEq)]
+pub enum WithdrawalPricePolicy {
+    /// Lp tokens are burned at request time for the reserve tokens they are worth then
+    AtRequest,
+    /// Lp tokens are escrowed and burned at fulfilment for the reserve tokens they are worth then
+    AtFulfil,
+}
+
+/// Where a withdrawal request is in its lifecycle
+#[repr(u8)]
+#[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, PartialEq)]
+pub enum WithdrawalRequestStatus {
+    /// Waiting in the queue to be fulfilled
+    Queued,
+    /// Fulfilled into the queue's reserve token escrow for the owner to claim
+    Claimable,
+    /// Cancelled by the owner, closed without a transfer once it reaches the head of the queue
+    Cancelled,
+}
+
 bitflags::bitflags! {
     pub struct VaultFlags: u32 {
         const HALT_RECONCILES = 1 << 0;
         const HALT_REFRESHED = 1 << 1;
+        /// Also halts the withdrawal queue and claims of withdrawals and referral fees
         const HALT_DEPOSITS_WITHDRAWS = 1 << 2;
         const HALT_ALL = Self::HALT_RECONCILES.bits | Self::HALT_REFRESHED.bits | Self::HALT_DEPOSITS_WITHDRAWS.bits;
     }
 }
 
+#[assert_size(aligns, 168)]
+#[repr(C, align(8))]
+#[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, Default)]
+pub struct YieldSource {
+    /// Adapter used to interact with the lending market
+    pub provider: Provider,
+
+    /// Whether rebalance can allocate to this yield source
+    pub enabled: bool,
+
+    _padding: [u8; 6],
+
+    /// Program that owns the reserve
+    pub lending_program: Pubkey,
+
+    /// Reserve of the lending market
+    pub reserve: Pubkey,
+
+    /// Token account for the vault's lp tokens of this yield source
+    pub lp_token: Pubkey,
+
+    pub target_allocation: SlotTrackecValue,
+
+    pub actual_allocation: SlotTrackecValue,
+}
+
+impl YieldSource {
+    pub fn new(
+        provider: Provider,
+        lending_program: Pubkey,
+        reserve: Pubkey,
+        lp_token: Pubkey,
+    ) -> Self {
+        Self {
+            provider,
+            enabled: true,
+            _padding: [0; 6],
+            lending_program,
+            reserve,
+            lp_token,
+            target_allocation: SlotTrackecValue::default(),
+            actual_allocation: SlotTrackecValue::default(),
+        }
+    }
+
+    /// Returns if this slot holds a yield source
+    pub fn is_registered(&self) -> bool {
+        self.reserve != Pubkey::default()
+    }
+}
+
 #[assert_size(aligns, 72)]
 #[repr(C, align(8))]
 #[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, Default)]
-pub struct Allocations {
-    pub solend: SlotTrackecValue,
-    pub port: SlotTrackecValue,
-    pub jet: SlotTrackecValue,
+pub struct FeeRecipient {
+    /// Vault lp token account that receives the fees
+    pub token_account: Pubkey,
+
+    /// Owner of the token account, whose associated token accounts receive
+    /// the fees that are not paid in lp tokens
+    pub owner: Pubkey,
+
+    /// Share of the non-referral fees in bps
+    pub share_bps: u16,
+
+    _padding: [u8; 6],
 }
-impl_provider_index!(Allocations, SlotTrackecValue);
 
-impl Allocations {
-    pub fn from_container(c: AssetContainer<u64>, slot: u64) -> Self {
-        Provider::iter().fold(Self::default(), |mut acc, provider| {
-            acc[provider].update(c[provider], slot);
-            acc
+impl FeeRecipient {
+    pub fn new(token_account: Pubkey, owner: Pubkey, share_bps: u16) -> Self {
+        Self {
+            token_account,
+            owner,
+            share_bps,
+            _padding: [0; 6],
+        }
+    }
+
+    /// Returns if this slot holds a fee recipient
+    pub fn is_registered(&self) -> bool {
+        self.token_account != Pubkey::default()
+    }
+}
+
+/// Returns the account at `address` among the given accounts if it is there
+/// Fails if it is there but is not writable or does not deserialize
+fn find_writable_account<'info, T>(
+    accounts: &[AccountInfo<'info>],
+    address: &Pubkey,
+) -> Result<Option<Account<'info, T>>>
+where
+    T: AccountSerialize + AccountDeserialize + Owner + Clone,
+{
+    accounts
+        .iter()
+        .find(|account| account.key == address)
+        .map(|account| {
+            account.is_writable.ok_or(ErrorCode::InvalidAccount)?;
+            Account::try_from(account)
         })
+        .transpose()
+}
+
+/// Per-user account that deposits and withdraws update once it has been initialized
+/// Derived from the vault, b"position" and the user
+#[account]
+#[derive(Debug, Default)]
+pub struct UserPosition {
+    pub vault: Pubkey,
+
+    pub owner: Pubkey,
+
+    /// Lp tokens minted through deposits that passed this position in, less those withdrawn
+    /// Withdraws above this amount are charged the full withdrawal fee
+    /// Lp tokens transferred out of the owner's wallet are not taken off it
+    pub lp_token_amount: u64,
+
+    /// Slot of the last deposit that passed this position in
+    pub last_deposit_slot: u64,
+
+    /// Reserve tokens deposited through deposits that passed this position in
+    pub total_deposited: u64,
+
+    /// Reserve tokens transferred to the owner through withdraws that passed this position in
+    pub total_withdrawn: u64,
+
+    /// Lp token weighted average share price that the tracked lp tokens were minted at
+    /// Scaled by SHARE_PRICE_SCALE
+    pub entry_share_price: u64,
+
+    /// Owner of the referrer that deposits through this position were credited to
+    pub referrer: Pubkey,
+
+    /// Reserve tokens credited to the referrer that the tracked lp tokens have not withdrawn yet
+    pub referred_value: u64,
+}
+
+impl UserPosition {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 8 + 32 + 8;
+
+    pub fn address(vault: &Pubkey, owner: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[vault.as_ref(), b"position".as_ref(), owner.as_ref()],
+            &crate::ID,
+        )
+        .0
+    }
+
+    /// Returns the position of `owner` in `vault` if it is among the given accounts
+    pub fn from_accounts<'info>(
+        accounts: &[AccountInfo<'info>],
+        vault: &Pubkey,
+        owner: &Pubkey,
+    ) -> Result<Option<Account<'info, UserPosition>>> {
+        find_writable_account(accounts, &Self::address(vault, owner))
+    }
+
+    /// Returns the position at the address of `account` if it has been initialized
+    /// The address has to be checked by the caller
+    pub fn if_initialized<'info>(
+        account: &AccountInfo<'info>,
+    ) -> Result<Option<Account<'info, UserPosition>>> {
+        if account.owner != &crate::ID {
+            return Ok(None);
+        }
+        Account::try_from(account).map(Some)
+    }
+
+    /// Records a deposit of `reserve_tokens` that minted `lp_tokens` in `slot`
+    pub fn record_deposit(&mut self, reserve_tokens: u64, lp_tokens: u64, slot: u64) -> Result<()> {
+        self.entry_share_price = calc_entry_share_price(
+            self.entry_share_price,
+            self.lp_token_amount,
+            reserve_tokens,
+            lp_tokens,
+        )
+        .ok_or(ErrorCode::MathError)?;
+        self.lp_token_amount = self
+            .lp_token_amount
+            .checked_add(lp_tokens)
+            .ok_or(ErrorCode::OverflowError)?;
+        self.total_deposited = self
+            .total_deposited
+            .checked_add(reserve_tokens)
+            .ok_or(ErrorCode::OverflowError)?;
+        // Restarts the withdrawal fee decay for all of the tracked lp tokens
+        self.last_deposit_slot = slot;
+        Ok(())
+    }
+
+    /// Records a deposit of `reserve_tokens` credited to `referrer`
+    /// A position stays with the first referrer that it was credited to
+    pub fn record_referral(&mut self, referrer: &Pubkey, reserve_tokens: u64) -> Result<()> {
+        (self.referrer == Pubkey::default() || self.referrer == *referrer)
+            .ok_or(ErrorCode::InvalidReferrer)?;
+        self.referrer = *referrer;
+        self.referred_value = self
+            .referred_value
+            .checked_add(reserve_tokens)
+            .ok_or(ErrorCode::OverflowError)?;
+        Ok(())
+    }
+
+    /// Records a withdraw that burned `lp_tokens` and transferred `reserve_tokens` to the owner
+    /// Lp tokens that were not tracked leave the entry share price as is
+    pub fn record_withdraw(&mut self, reserve_tokens: u64, lp_tokens: u64) -> Result<()> {
+        self.lp_token_amount = self.lp_token_amount.saturating_sub(lp_tokens);
+        self.total_withdrawn = self
+            .total_withdrawn
+            .checked_add(reserve_tokens)
+            .ok_or(ErrorCode::OverflowError)?;
+        Ok(())
+    }
+
+    /// Records `lp_tokens` that a cancelled withdrawal request returned to the position
+    /// The withdrawal fee keeps decaying from the last deposit
+    pub fn record_cancelled_withdraw(&mut self, lp_tokens: u64) -> Result<()> {
+        self.lp_token_amount = self
+            .lp_token_amount
+            .checked_add(lp_tokens)
+            .ok_or(ErrorCode::OverflowError)?;
+        Ok(())
+    }
+
+    /// Slots since the last deposit that passed this position in
+    pub fn slots_since_deposit(&self, slot: u64) -> Result<u64> {
+        slot.checked_sub(self.last_deposit_slot)
+            .ok_or_else(|| ErrorCode::MathError.into())
+    }
+}
+
+/// Withdrawal queued until the vault holds enough reserve tokens to fulfil it
+/// Derived from the vault, b"withdrawal_request" and the queue index
+#[account]
+#[derive(Debug)]
+pub struct WithdrawalRequest {
+    pub vault: Pubkey,
+
+    pub owner: Pubkey,
+
+    /// Position in the withdrawal queue
+    pub index: u64,
+
+    pub status: WithdrawalRequestStatus,
+
+    pub price_policy: WithdrawalPricePolicy,
+
+    /// Lp tokens burned at request time or escrowed until fulfilment, depending on the policy
+    pub lp_token_amount: u64,
+
+    /// Reserve tokens locked in at request time, zero when priced at fulfilment
+    pub reserve_token_amount: u64,
+
+    /// Withdrawal fee at request time, charged the same way as on a withdraw
+    pub withdrawal_fee_bps: u64,
+
+    pub decayed_withdrawal_fee_bps: u64,
+
+    pub decayed_lp_tokens: u64,
+
+    pub request_slot: u64,
+
+    /// Reserve tokens held in the queue's reserve token escrow for the owner to claim,
+    /// when the owner's associated token account could not receive them at fulfilment
+    pub claimable_amount: u64,
+
+    /// Lp tokens of the request that the owner's position tracked, restored on cancel
+    pub position_lp_tokens: u64,
+
+    /// Value taken off the owner's referrer at request time, restored on cancel
+    pub released_referred_value: u64,
+}
+
+impl WithdrawalRequest {
+    pub const LEN: usize = 32 * 2 + 8 + 1 + 1 + 8 * 9;
+}
+
+/// Account that deposits can name to share in the referral fees
+/// Derived from the vault, b"referrer" and the owner
+#[account]
+#[derive(Debug, Default)]
+pub struct Referrer {
+    pub vault: Pubkey,
+
+    pub owner: Pubkey,
+
+    /// Reserve tokens deposited with this referrer
+    pub referred_value: u64,
+
+    /// Vault referral fees per referred value at the last settlement
+    /// Indexed by fee denomination
+    pub referral_fees_per_value: [u128; 2],
+
+    /// Referral fees settled but not yet claimed
+    /// Indexed by fee denomination
+    pub unclaimed_fees: [u64; 2],
+}
+
+impl Referrer {
+    pub const LEN: usize = 32 + 32 + 8 + 16 * 2 + 8 * 2;
+
+    pub fn address(vault: &Pubkey, owner: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[vault.as_ref(), b"referrer".as_ref(), owner.as_ref()],
+            &crate::ID,
+        )
+        .0
+    }
+
+    /// Returns the referrer account of `owner` in `vault` if it is among the given accounts
+    pub fn from_accounts<'info>(
+        accounts: &[AccountInfo<'info>],
+        vault: &Pubkey,
+        owner: &Pubkey,
+    ) -> Result<Option<Account<'info, Referrer>>> {
+        find_writable_account(accounts, &Self::address(vault, owner))
+    }
+
+    /// Moves the referral fees accrued on the referred value since the last settlement
+    /// into the unclaimed fees
+    pub fn settle(&mut self, vault: &Vault) -> Result<()> {
+        for (index, vault_fees_per_value) in vault.referral_fees_per_value.iter().enumerate() {
+            let owed = vault_fees_per_value
+                .checked_sub(self.referral_fees_per_value[index])
+                .and_then(|delta| calc_referral_fees_owed(self.referred_value, delta))
+                .ok_or(ErrorCode::MathError)?;
+            self.unclaimed_fees[index] = self.unclaimed_fees[index]
+                .checked_add(owed)
+                .ok_or(ErrorCode::OverflowError)?;
+            self.referral_fees_per_value[index] = *vault_fees_per_value;
+        }
+        Ok(())
     }
 }
 
@@ -216,9 +992,9 @@ pub struct SlotTrackecValue {
 }
 
 impl SlotTrackecValue {
-    pub fn update(&mut self, value: u64, slot: u64) {
+    pub fn update(&mut self, value: u64, clock: &Clock) {
         self.value = value;
-        self.last_update.update_slot(slot);
+        self.last_update.update(clock);
     }
 
     pub fn reset(&mut self) {
@@ -230,20 +1006,22 @@ impl SlotTrackecValue {
 // Number of slots to consider stale after
 pub const STALE_AFTER_SLOTS_ELAPSED: u64 = 2;
 
-#[assert_size(aligns, 16)]
+#[assert_size(aligns, 24)]
 #[repr(C, align(8))]
 #[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, Default)]
 pub struct LastUpdate {
     pub slot: u64,
+    pub unix_timestamp: i64,
     pub stale: bool,
     _padding: [u8; 7],
 }
 
 impl LastUpdate {
     /// Create new last update
-    pub fn new(slot: u64) -> Self {
+    pub fn new(clock: &Clock) -> Self {
         Self {
-            slot,
+            slot: clock.slot,
+            unix_timestamp: clock.unix_timestamp,
             stale: true,
             _padding: [0_u8; 7],
         }
@@ -255,9 +1033,18 @@ impl LastUpdate {
             .ok_or_else(|| ErrorCode::MathError.into())
     }
 
-    /// Set last update slot
-    pub fn update_slot(&mut self, slot: u64) {
-        self.slot = slot;
+    /// Return seconds elapsed since given unix timestamp
+    pub fn seconds_elapsed(&self, unix_timestamp: i64) -> Result<u64> {
+        unix_timestamp
+            .checked_sub(self.unix_timestamp)
+            .and_then(|seconds| u64::try_from(seconds).ok())
+            .ok_or_else(|| ErrorCode::MathError.into())
+    }
+
+    /// Set last update slot and unix timestamp
+    pub fn update(&mut self, clock: &Clock) {
+        self.slot = clock.slot;
+        self.unix_timestamp = clock.unix_timestamp;
         self.stale = false;
     }
 